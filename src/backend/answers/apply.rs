@@ -0,0 +1,139 @@
+use slab_tree::{NodeId, Tree};
+use crate::backend::answers::Answers;
+use crate::backend::tree::nodes::{LeafNodeType, NodePath};
+use crate::error::{Error, Result};
+
+/// The kind of question an answer is meant for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expect {
+    /// A text input gets the value, an option selects it.
+    Any,
+    Text,
+    Select,
+}
+
+pub fn apply_answers(tree: &mut Tree<LeafNodeType>, answers: &Answers) -> Result<()> {
+    let mut errors = Vec::new();
+    // the multi-select options answered so far, the first answer replaces their selection
+    let mut answered = Vec::new();
+    for (key, value) in answers.entries() {
+        if let Err(e) = apply(tree, key, value, Expect::Any, Some(&mut answered)) {
+            errors.push(e.to_string());
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::Validation(format!("{} answer(s) could not be applied\n{}", errors.len(), errors.join("\n"))))
+    }
+}
+
+pub fn apply_answer(tree: &mut Tree<LeafNodeType>, key: &str, value: &str, expect: Expect) -> Result<()> {
+    apply(tree, key, value, expect, None)
+}
+
+fn apply(tree: &mut Tree<LeafNodeType>, key: &str, value: &str, expect: Expect, mut answered: Option<&mut Vec<NodeId>>) -> Result<()> {
+    for id in questions(tree, key)? {
+        let Some(mut node) = tree.get_mut(id) else { continue };
+        match node.data() {
+            LeafNodeType::TextInput { name: _name, input, .. } if expect != Expect::Select => *input = value.to_string(),
+            LeafNodeType::Option { name: _name, options, .. } if expect != Expect::Text => {
+                if !options.items().iter().any(|(item, _)| item == value) {
+                    return Err(Error::Validation(format!(
+                        "'{}' is not an option of '{}', choose one of {}", value, key, options.join_names_with(", ")
+                    )));
+                }
+                if let Some(answered) = answered.as_deref_mut().filter(|_| options.is_multiselect()) {
+                    if !answered.contains(&id) {
+                        options.clear();
+                        answered.push(id);
+                    }
+                }
+                options.select(value);
+            }
+            LeafNodeType::Option { .. } => {
+                return Err(Error::Validation(format!("'{}' is a choice, select one of its options", key)));
+            }
+            LeafNodeType::TextInput { .. } => {
+                return Err(Error::Validation(format!("'{}' is a text input and has no options to select", key)));
+            }
+            LeafNodeType::Text { .. } => {
+                return Err(Error::Validation(format!("'{}' is a fixed folder and takes no answer", key)));
+            }
+            LeafNodeType::File { .. } => {
+                return Err(Error::Validation(format!("'{}' is a file and takes no answer", key)));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The nodes `key` answers. A key that is a whole path answers the nodes at that path, one
+/// that is only the end of a path has to match a single path. Several nodes share a path in
+/// the subtrees of different choices.
+fn questions(tree: &Tree<LeafNodeType>, key: &str) -> Result<Vec<NodeId>> {
+    let ids = tree.find_nodes(key);
+    if ids.is_empty() {
+        return Err(Error::Validation(format!("the template has no question called '{}'", key)));
+    }
+    let exact: Vec<NodeId> = ids.iter().copied().filter(|id| tree.node_path(*id) == key.trim_matches('/')).collect();
+    if !exact.is_empty() {
+        return Ok(exact);
+    }
+    let mut paths: Vec<String> = Vec::new();
+    for path in ids.iter().map(|id| tree.node_path(*id)) {
+        if !paths.contains(&path) {
+            paths.push(path);
+        }
+    }
+    if paths.len() > 1 {
+        return Err(Error::Validation(format!("'{}' could be any of {}, use the whole path", key, paths.join(", "))));
+    }
+    Ok(ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use crate::backend::answers::collect_answers;
+    use crate::backend::yaml::{load_yaml, load_yaml_str};
+    use super::*;
+
+    #[test]
+    fn test_ambiguous_key() {
+        let template = "\
+project:
+  default_location: /tmp
+  children:
+    - Song:
+        children:
+          - Name:
+              options: \"\"
+    - Beat:
+        children:
+          - Name:
+              options: \"\"
+";
+        let mut tree = load_yaml_str(template, "t.yaml").unwrap().0;
+        let err = apply_answer(&mut tree, "Name", "Tune", Expect::Any).unwrap_err();
+        assert_eq!(err.to_string(), "'Name' could be any of Song/Name, Beat/Name, use the whole path");
+        apply_answer(&mut tree, "Beat/Name", "Tune", Expect::Any).unwrap();
+    }
+
+    #[test]
+    fn test_multiselect_answers_replace_the_selection() {
+        let mut tree = load_yaml(Path::new("test/multiselect.yaml")).unwrap().0;
+        apply_answer(&mut tree, "DAW", "Ableton", Expect::Select).unwrap();
+        apply_answer(&mut tree, "DAW", "Logic", Expect::Select).unwrap();
+
+        let mut answers = Answers::default();
+        answers.push("DAW", "Fl Studio");
+        answers.push("DAW", "Logic");
+        apply_answers(&mut tree, &answers).unwrap();
+        let daws: Vec<String> = collect_answers(&tree).entries().iter()
+            .filter(|(key, _)| key.ends_with("DAW"))
+            .map(|(_, value)| value.clone())
+            .collect();
+        assert_eq!(daws, vec!["Fl Studio", "Logic"]);
+    }
+}
@@ -0,0 +1,105 @@
+use std::fs;
+use std::path::Path;
+use serde_yaml::{Mapping, Value};
+use crate::backend::answers::Answers;
+use crate::error::{Error, Result};
+
+pub fn read_answers(path: &Path) -> Result<Answers> {
+    let answers_error = |message: String| Error::Answers { path: path.to_path_buf(), message };
+    let content = fs::read_to_string(path).map_err(|e| answers_error(e.to_string()))?;
+    // json is a subset of yaml, so both are read the same way
+    let value: Value = serde_yaml::from_str(&content).map_err(|e| answers_error(e.to_string()))?;
+
+    let mut answers = Answers::default();
+    let mapping = match value {
+        Value::Null => return Ok(answers),
+        Value::Mapping(mapping) => mapping,
+        _ => return Err(answers_error(String::from("the answers have to be a mapping of `Name: value` pairs"))),
+    };
+    for (key, value) in mapping {
+        let key = scalar_to_string(&key)
+            .ok_or_else(|| answers_error(format!("the key {:?} is not a node name or path", key)))?;
+        // a list checks several items of a multi-select option
+        let values = match &value {
+            Value::Sequence(items) => items.iter().map(scalar_to_string).collect(),
+            other => scalar_to_string(other).map(|v| vec![v]),
+        };
+        let values = values
+            .ok_or_else(|| answers_error(format!("the answer for '{}' has to be a value or a list of values", key)))?;
+        for value in values {
+            answers.push(key.clone(), value);
+        }
+    }
+    Ok(answers)
+}
+
+pub fn write_answers(path: &Path, answers: &Answers) -> Result<()> {
+    let content = format_answers(answers)
+        .map_err(|e| Error::Answers { path: path.to_path_buf(), message: e.to_string() })?;
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent).map_err(|source| Error::Filesystem { path: parent.to_path_buf(), source })?;
+    }
+    fs::write(path, content).map_err(|source| Error::Filesystem { path: path.to_path_buf(), source })
+}
+
+pub fn format_answers(answers: &Answers) -> serde_yaml::Result<String> {
+    let mut mapping = Mapping::new();
+    for (key, value) in answers.entries() {
+        let key = Value::String(key.clone());
+        let value = Value::String(value.clone());
+        // repeated keys are the items of a multi-select option
+        match mapping.get_mut(&key) {
+            Some(Value::Sequence(items)) => items.push(value),
+            Some(previous) => *previous = Value::Sequence(vec![previous.clone(), value]),
+            None => { mapping.insert(key, value); }
+        }
+    }
+    serde_yaml::to_string(&mapping)
+}
+
+fn scalar_to_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use super::*;
+
+    #[test]
+    fn test_read_yaml_and_json() {
+        let answers = read_answers(Path::new("test/working_answers.yaml")).unwrap();
+        assert_eq!(answers.entries()[1], (String::from("ProjectName"), String::from("My Song")));
+
+        let json_path = std::env::temp_dir().join("scaffolder_answers.json");
+        fs::write(&json_path, r#"{"ProjectName": "Tune", "Take": 3}"#).unwrap();
+        let answers = read_answers(&json_path).unwrap();
+        assert_eq!(answers.entries()[1], (String::from("Take"), String::from("3")));
+        fs::remove_file(json_path).unwrap();
+    }
+
+    #[test]
+    fn test_write_and_read_back() {
+        let path = std::env::temp_dir().join("scaffolder_recorded").join("answers.yaml");
+        let mut answers = Answers::default();
+        answers.push("Location", "~/music");
+        answers.push("ProjectType/ProjectName", "2024: a song");
+        answers.push("Output", "Ableton");
+        answers.push("Output", "Fl Studio");
+        write_answers(&path, &answers).unwrap();
+        assert!(fs::read_to_string(&path).unwrap().contains("Output:\n- Ableton\n- Fl Studio"));
+        assert_eq!(read_answers(&path).unwrap(), answers);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_answers_file() {
+        assert!(read_answers(&PathBuf::from("test/missing_answers.yaml")).is_err());
+        assert!(read_answers(Path::new("test/working.yaml")).is_err());
+    }
+}
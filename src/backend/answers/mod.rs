@@ -0,0 +1,73 @@
+use std::path::Path;
+use slab_tree::Tree;
+use crate::backend::tree::nodes::LeafNodeType;
+use crate::error::{Error, Result};
+
+mod apply;
+mod file;
+mod record;
+
+pub use apply::Expect;
+
+/// File name the answers of an interactive run are saved under, inside the project folder.
+pub const RECORD_FILE_NAME: &str = ".scaffolder-answers.yaml";
+
+/// Answers to the questions of a template, keyed by node name or node path.
+///
+/// The keys are matched like [`NodePath::find_nodes`](crate::backend::tree::nodes::NodePath::find_nodes),
+/// later entries win over earlier ones. A multi-select option checks the items of all its entries,
+/// they replace the items that were checked before.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Answers {
+    entries: Vec<(String, String)>,
+}
+
+impl Answers {
+    pub fn push(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.entries.push((key.into(), value.into()));
+    }
+
+    pub fn extend(&mut self, other: Answers) {
+        self.entries.extend(other.entries);
+    }
+
+    pub fn entries(&self) -> &[(String, String)] {
+        &self.entries
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Reads answers from a yaml or json file.
+pub fn read_answers(path: &Path) -> Result<Answers> {
+    file::read_answers(path)
+}
+
+/// Writes answers in the format [`read_answers`] understands.
+pub fn write_answers(path: &Path, answers: &Answers) -> Result<()> {
+    file::write_answers(path, answers)
+}
+
+/// The answers as the yaml [`write_answers`] writes.
+pub fn format_answers(answers: &Answers) -> Result<String> {
+    file::format_answers(answers).map_err(|e| Error::Validation(format!("could not write the answers as yaml: {}", e)))
+}
+
+/// Collects the answers given in `tree`, keyed by node path.
+pub fn collect_answers(tree: &Tree<LeafNodeType>) -> Answers {
+    record::collect_answers(tree)
+}
+
+/// Fills the text inputs and option selections of `tree` with `answers`, a key matching the
+/// ends of several different paths is an error.
+pub fn apply_answers(tree: &mut Tree<LeafNodeType>, answers: &Answers) -> Result<()> {
+    apply::apply_answers(tree, answers)
+}
+
+/// Applies a single answer, `expect` restricts which kind of question it may answer. A
+/// multi-select option keeps the items checked before.
+pub fn apply_answer(tree: &mut Tree<LeafNodeType>, key: &str, value: &str, expect: Expect) -> Result<()> {
+    apply::apply_answer(tree, key, value, expect)
+}
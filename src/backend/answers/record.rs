@@ -0,0 +1,49 @@
+use slab_tree::Tree;
+use crate::backend::answers::Answers;
+use crate::backend::tree::condition::NodeEnabled;
+use crate::backend::tree::nodes::{LeafNodeType, NodePath};
+
+pub fn collect_answers(tree: &Tree<LeafNodeType>) -> Answers {
+    let mut answers = Answers::default();
+    let Some(root) = tree.root() else { return answers };
+    // answers of disabled branches were not used to build the project
+    for node in root.traverse_pre_order().filter(|n| tree.is_enabled(n.node_id())) {
+        // a multi-select option is recorded with one entry per checked item
+        let node_answers = match node.data() {
+            LeafNodeType::TextInput { name: _name, input, .. } => vec![input.clone()],
+            LeafNodeType::Option { name: _name, options, .. } => options.selected(),
+            LeafNodeType::Text { .. } | LeafNodeType::File { .. } => Vec::new(),
+        };
+        for answer in node_answers.into_iter().filter(|a| !a.is_empty()) {
+            answers.push(tree.node_path(node.node_id()), answer);
+        }
+    }
+    answers
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use crate::backend::answers::{apply_answers, read_answers};
+    use crate::backend::yaml::load_yaml;
+    use super::*;
+
+    #[test]
+    fn test_recorded_answers_replay() {
+        let mut tree = load_yaml(Path::new("test/working.yaml")).unwrap().0;
+        apply_answers(&mut tree, &read_answers(Path::new("test/working_answers.yaml")).unwrap()).unwrap();
+        let recorded = collect_answers(&tree);
+        let keys: Vec<&str> = recorded.entries().iter().map(|(key, _)| key.as_str()).collect();
+        assert_eq!(keys, vec![
+            "Location",
+            "ProjectType",
+            "ProjectType/ProjectName",
+            "ProjectType/ProjectName/Project",
+            "ProjectType/ProjectName/Output",
+        ]);
+
+        let mut replayed = load_yaml(Path::new("test/working.yaml")).unwrap().0;
+        apply_answers(&mut replayed, &recorded).unwrap();
+        assert_eq!(collect_answers(&replayed), recorded);
+    }
+}
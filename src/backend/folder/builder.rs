@@ -1,50 +1,333 @@
-use std::fs;
-use std::path::PathBuf;
-use slab_tree::{NodeRef, Tree};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use crate::backend::folder::plan::{Operation, Plan};
+use crate::error::{Error, Result};
 
-pub fn build_folder_structure(tree: Tree<String>) -> () {
-    let root = tree.root().expect("Error, tree has no root");
-    let dir_path = PathBuf::new();
-    walk_tree(root, &dir_path);
+/// What was undone after a build failed halfway.
+#[derive(Debug, Default)]
+pub struct Rollback {
+    /// The folders and files the build had created, all of them are gone again.
+    pub removed: Vec<PathBuf>,
+    /// The paths that could not be removed and are still on disk.
+    pub left: Vec<(PathBuf, io::Error)>,
 }
 
-fn walk_tree(node: NodeRef<String>, parent_path: &PathBuf){
-    let node_name = node.data();
-    let dir_path = parent_path.join(node_name);
-    create_folder(&dir_path);
-    node.children().for_each(|child| {
-        walk_tree(child, &dir_path)
-    });
+impl Rollback {
+    pub fn is_clean(&self) -> bool {
+        self.left.is_empty()
+    }
+}
+
+/// Carries out the steps of the plan and removes everything they created again if one of
+/// them fails, paths that existed before are never touched.
+pub fn execute_plan(plan: &Plan) -> Result<()> {
+    let mut transaction = Transaction::default();
+    match plan.steps.iter().try_for_each(|step| transaction.carry_out(&step.operation)) {
+        Ok(()) => Ok(()),
+        Err(Error::Filesystem { path, source }) => Err(Error::Build { path, source, rollback: transaction.rollback() }),
+        Err(e) => {
+            transaction.rollback();
+            Err(e)
+        }
+    }
 }
 
-fn create_folder(name: &PathBuf) {
-    fs::create_dir_all(name).expect(format!("could not create folder: {:?}", name).as_str())
+/// Records every path the build creates, in order.
+#[derive(Default)]
+struct Transaction {
+    created: Vec<PathBuf>,
+}
+
+impl Transaction {
+    fn carry_out(&mut self, operation: &Operation) -> Result<()> {
+        match operation {
+            Operation::Mkdir { path } => self.create_folder(path),
+            Operation::WriteFile { path, content } => self.create(path, |file| file.write_all(content.as_bytes())),
+            Operation::Copy { path, from } => {
+                let mut source = File::open(from).map_err(|source| Error::Filesystem { path: from.clone(), source })?;
+                let permissions = source.metadata().map_err(|source| Error::Filesystem { path: from.clone(), source })?.permissions();
+                self.create(path, |file| {
+                    io::copy(&mut source, file)?;
+                    file.set_permissions(permissions)
+                })
+            }
+            Operation::Symlink { path, target } => self.create_link(path, target),
+            Operation::Chmod { path, mode } => {
+                // the mode of a path that was there before could not be restored by a rollback
+                if !self.created.contains(path) {
+                    let source = io::Error::new(io::ErrorKind::PermissionDenied, "only paths created by this build can change their mode");
+                    return Err(Error::Filesystem { path: path.clone(), source });
+                }
+                set_mode(path, *mode).map_err(|source| Error::Filesystem { path: path.clone(), source })
+            }
+            Operation::Hook { path, command } => run_hook(path, command).map_err(|source| Error::Filesystem { path: path.clone(), source }),
+        }
+    }
+
+    fn create_folder(&mut self, name: &Path) -> Result<()> {
+        // one folder at a time, so each missing ancestor is recorded as well
+        let missing: Vec<&Path> = name.ancestors()
+            .take_while(|path| !path.as_os_str().is_empty() && !path.is_dir())
+            .collect();
+        for path in missing.into_iter().rev() {
+            fs::create_dir(path).map_err(|source| Error::Filesystem { path: path.to_path_buf(), source })?;
+            self.created.push(path.to_path_buf());
+        }
+        Ok(())
+    }
+
+    /// Creates the file `name` and fills it, a file that is already there is never overwritten.
+    fn create(&mut self, name: &Path, fill: impl FnOnce(&mut File) -> io::Result<()>) -> Result<()> {
+        // names with a `/` from substituted paths can point into folders that do not exist yet
+        if let Some(parent) = name.parent() {
+            self.create_folder(parent)?;
+        }
+        let to_error = |source| Error::Filesystem { path: name.to_path_buf(), source };
+        let mut file = OpenOptions::new().write(true).create_new(true).open(name).map_err(to_error)?;
+        // recorded before it is filled, so a half written file is removed as well
+        self.created.push(name.to_path_buf());
+        fill(&mut file).map_err(to_error)
+    }
+
+    /// Creates the link `name`, a path that is already there is never replaced.
+    fn create_link(&mut self, name: &Path, target: &Path) -> Result<()> {
+        if let Some(parent) = name.parent() {
+            self.create_folder(parent)?;
+        }
+        symlink(target, name).map_err(|source| Error::Filesystem { path: name.to_path_buf(), source })?;
+        self.created.push(name.to_path_buf());
+        Ok(())
+    }
+
+    /// Removes the created paths, the newest first. Created folders go with whatever is in
+    /// them, hooks can leave files the build did not record.
+    fn rollback(self) -> Rollback {
+        let mut rollback = Rollback::default();
+        for path in self.created.into_iter().rev() {
+            let removed = match path.symlink_metadata().is_ok_and(|m| m.is_dir()) {
+                true => fs::remove_dir_all(&path),
+                false => fs::remove_file(&path),
+            };
+            match removed {
+                Ok(()) => rollback.removed.push(path),
+                Err(e) => rollback.left.push((path, e)),
+            }
+        }
+        rollback
+    }
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, path: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, path)
+}
+
+#[cfg(not(unix))]
+fn symlink(_target: &Path, _path: &Path) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "symlinks are only supported on unix"))
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+}
+
+// there are no unix permissions to set
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _mode: u32) -> io::Result<()> {
+    Ok(())
+}
+
+fn run_hook(dir: &Path, command: &str) -> io::Result<()> {
+    let status = Command::new("sh").arg("-c").arg(command).current_dir(dir).status()?;
+    match status.success() {
+        true => Ok(()),
+        false => Err(io::Error::other(format!("`{}` failed with {}", command, status))),
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use slab_tree::{NodeMut, TreeBuilder};
+    use crate::backend::answers::{apply_answer, Expect};
+    use crate::backend::folder::{add_hooks, build_plan, resolve_root};
+    use crate::backend::tree::resolve::resolve_tree;
+    use crate::backend::yaml::load_yaml;
+    use slab_tree::{NodeMut, Tree, TreeBuilder};
+    use crate::backend::folder::build_folder_structure;
+    use crate::backend::folder::conflict::Status;
+    use crate::backend::folder::plan::Step;
+    use crate::backend::tree::nodes::{BuildNode, FileContent};
     use super::*;
 
     #[test]
     fn test_build_folder_structure() {
-        let mut tree: Tree<String> = TreeBuilder::new()
-            .with_root(String::from("root"))
+        let mut tree: Tree<BuildNode> = TreeBuilder::new()
+            .with_root(BuildNode::folder("root"))
             .build();
         let mut root = tree.root_mut().unwrap();
         append_children(2, &mut root);
-        build_folder_structure(tree);
+        build_folder_structure(tree).unwrap();
+    }
+
+    #[test]
+    fn test_build_files() {
+        let location = std::env::temp_dir().join("scaffolder_build_files");
+        let _ = fs::remove_dir_all(&location);
+        let content_path = location.join("content.txt");
+        fs::create_dir_all(&location).unwrap();
+        fs::write(&content_path, "copied").unwrap();
+
+        let mut tree: Tree<BuildNode> = TreeBuilder::new()
+            .with_root(BuildNode::folder(location.to_string_lossy()))
+            .build();
+        let mut root = tree.root_mut().unwrap();
+        let mut docs = root.append(BuildNode::folder("docs"));
+        docs.append(BuildNode::file("README.md", FileContent::Inline(String::from("# Title\n"))));
+        docs.append(BuildNode::file("copy.txt", FileContent::Path(content_path)));
+        docs.append(BuildNode::file(".keep", FileContent::Empty));
+        build_folder_structure(tree).unwrap();
+
+        assert_eq!(fs::read_to_string(location.join("docs/README.md")).unwrap(), "# Title\n");
+        assert_eq!(fs::read_to_string(location.join("docs/copy.txt")).unwrap(), "copied");
+        assert_eq!(fs::read_to_string(location.join("docs/.keep")).unwrap(), "");
+        fs::remove_dir_all(location).unwrap();
+    }
+
+    #[test]
+    fn test_rollback() {
+        let location = std::env::temp_dir().join("scaffolder_rollback");
+        let _ = fs::remove_dir_all(&location);
+        fs::create_dir_all(location.join("Song")).unwrap();
+        fs::write(location.join("Song/notes.txt"), "kept").unwrap();
+        // a file where a folder has to go stops the build halfway
+        fs::write(location.join("blocked"), "kept").unwrap();
+
+        let mut tree: Tree<BuildNode> = TreeBuilder::new()
+            .with_root(BuildNode::folder(location.to_string_lossy()))
+            .build();
+        let mut root = tree.root_mut().unwrap();
+        let mut song = root.append(BuildNode::folder("Song"));
+        song.append(BuildNode::folder("Tune/Stems"));
+        song.append(BuildNode::file("mix.wav", FileContent::Empty));
+        song.append(BuildNode::file("notes.txt", FileContent::Inline(String::from("overwritten"))));
+
+        // a file that is already there stops the build instead of being overwritten
+        let Err(Error::Build { path, rollback, .. }) = build_folder_structure(tree) else { panic!("the build has to fail") };
+        assert_eq!(path, location.join("Song/notes.txt"));
+        assert!(rollback.is_clean());
+        assert_eq!(rollback.removed, vec![location.join("Song/mix.wav"), location.join("Song/Tune/Stems"), location.join("Song/Tune")]);
+        assert_eq!(fs::read_to_string(location.join("Song/notes.txt")).unwrap(), "kept");
+        assert!(location.join("Song").is_dir());
+        assert!(!location.join("Song/Tune").exists());
+
+        let mut tree: Tree<BuildNode> = TreeBuilder::new()
+            .with_root(BuildNode::folder(location.to_string_lossy()))
+            .build();
+        let mut root = tree.root_mut().unwrap();
+        root.append(BuildNode::folder("Beat")).append(BuildNode::file("mix.wav", FileContent::Empty));
+        root.append(BuildNode::folder("blocked")).append(BuildNode::folder("Take"));
+
+        let Err(Error::Build { path, rollback, .. }) = build_folder_structure(tree) else { panic!("the build has to fail") };
+        assert_eq!(path, location.join("blocked"));
+        assert!(rollback.is_clean());
+        assert_eq!(rollback.removed, vec![location.join("Beat/mix.wav"), location.join("Beat")]);
+        assert_eq!(fs::read_to_string(location.join("blocked")).unwrap(), "kept");
+        fs::remove_dir_all(location).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_execute_plan() {
+        use std::os::unix::fs::PermissionsExt;
+        let location = std::env::temp_dir().join("scaffolder_execute_plan");
+        let _ = fs::remove_dir_all(&location);
+        let step = |operation| Step { operation, status: Status::New };
+        let mut plan = Plan { root: location.clone(), steps: vec![
+            step(Operation::Mkdir { path: location.join("bin") }),
+            step(Operation::WriteFile { path: location.join("bin/run"), content: String::from("#!/bin/sh\n") }),
+            step(Operation::Chmod { path: location.join("bin/run"), mode: 0o755 }),
+            step(Operation::Symlink { path: location.join("run"), target: PathBuf::from("bin/run") }),
+            step(Operation::Hook { path: location.clone(), command: String::from("touch hooked") }),
+        ] };
+        execute_plan(&plan).unwrap();
+        assert_eq!(fs::metadata(location.join("bin/run")).unwrap().permissions().mode() & 0o777, 0o755);
+        assert_eq!(fs::read_link(location.join("run")).unwrap(), PathBuf::from("bin/run"));
+        assert!(location.join("hooked").is_file());
+        fs::remove_dir_all(&location).unwrap();
+
+        // a failing hook rolls back the link and the folders with it, output of the hook included
+        plan.steps[4] = step(Operation::Hook { path: location.clone(), command: String::from("touch hooked; exit 3") });
+        let Err(Error::Build { rollback, .. }) = execute_plan(&plan) else { panic!("the hook has to fail") };
+        assert!(rollback.is_clean());
+        assert_eq!(rollback.removed.first(), Some(&location.join("run")));
+        assert!(!location.exists());
+
+        // the mode of a file that was there before is left alone
+        fs::create_dir_all(&location).unwrap();
+        fs::write(location.join("kept"), "").unwrap();
+        let plan = Plan { root: location.clone(), steps: vec![step(Operation::Chmod { path: location.join("kept"), mode: 0o700 })] };
+        assert!(matches!(execute_plan(&plan), Err(Error::Build { .. })));
+        fs::remove_dir_all(&location).unwrap();
     }
 
     /*fn get_node(name: &str) -> LeafNodeType {
         LeafNodeType::Text { name: name.to_string() }
     }*/
 
-    fn append_children(amt: u16, parent: &mut NodeMut<String>) {
+    fn append_children(amt: u16, parent: &mut NodeMut<BuildNode>) {
         for i in 0..amt {
-            let mut child = parent.append(format!("child{}", i+1));
-            child.append(format!("sub_child{}", i+1));
+            let mut child = parent.append(BuildNode::folder(format!("child{}", i+1)));
+            child.append(BuildNode::folder(format!("sub_child{}", i+1)));
+        }
+
+    }
+
+    /// The plan of the template at `path` with `ProjectName` answered, built at `location`.
+    fn template_plan(path: &str, location: &Path) -> Plan {
+        let (mut tree, settings) = load_yaml(Path::new(path)).unwrap();
+        apply_answer(&mut tree, "Location", &location.to_string_lossy(), Expect::Text).unwrap();
+        apply_answer(&mut tree, "ProjectName", "Tune", Expect::Text).unwrap();
+        let mut plan = resolve_tree(&tree).unwrap();
+        resolve_root(&mut plan).unwrap();
+        add_hooks(&mut plan, &settings.hooks);
+        build_plan(&plan).unwrap()
+    }
+
+    #[test]
+    fn test_build_assets() {
+        let location = std::env::temp_dir().join("scaffolder_build_assets");
+        let _ = fs::remove_dir_all(&location);
+        execute_plan(&template_plan("test/assets.yaml", &location)).unwrap();
+        let project = location.join("Tune");
+        assert_eq!(fs::read_to_string(project.join("Tune.als")).unwrap(), "ableton session stub\n");
+        assert!(project.join("Clicks/90bpm.wav").is_file() && project.join("Clicks/120bpm.wav").is_file());
+        // only the names of `render_names` copies are substituted
+        assert!(project.join("Artwork/Tune_cover.psd").is_file());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(project.join("Artwork/export.sh")).unwrap().permissions().mode() & 0o777, 0o755);
         }
+        fs::remove_dir_all(location).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_build_links_modes_and_hooks() {
+        use std::os::unix::fs::PermissionsExt;
+        let location = std::env::temp_dir().join("scaffolder_build_links");
+        let _ = fs::remove_dir_all(&location);
+        let plan = template_plan("test/files.yaml", &location);
+        assert_eq!(plan.steps.last().unwrap().operation, Operation::Hook { path: location.join("Tune"), command: String::from("touch .hooked") });
 
+        execute_plan(&plan).unwrap();
+        let project = location.join("Tune");
+        assert_eq!(fs::metadata(project.join("run.sh")).unwrap().permissions().mode() & 0o777, 0o755);
+        assert_eq!(fs::read_link(project.join("latest")).unwrap(), PathBuf::from("Tune.als"));
+        assert!(project.join(".hooked").is_file());
+        fs::remove_dir_all(location).unwrap();
     }
-}
\ No newline at end of file
+}
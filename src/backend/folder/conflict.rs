@@ -0,0 +1,185 @@
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use slab_tree::{NodeId, NodeRef, RemoveBehavior, Tree};
+use crate::backend::tree::nodes::BuildNode;
+
+/// What to do when folders or files of the project already exist on disk.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// Stops before anything is created.
+    Abort,
+    /// Builds into the existing folders and keeps the files that are already there.
+    Merge,
+    /// Builds next to them, as `Name_2`, `Name_3`, ...
+    Suffix,
+    /// Asks which of the others to use.
+    Prompt,
+}
+
+/// The names of the policies in templates and on the command line, in the order of [`ConflictPolicy::ALL`].
+pub const POLICY_NAMES: &[&str] = &["abort", "merge", "suffix", "prompt"];
+
+impl ConflictPolicy {
+    pub const ALL: [ConflictPolicy; 4] = [ConflictPolicy::Abort, ConflictPolicy::Merge, ConflictPolicy::Suffix, ConflictPolicy::Prompt];
+
+    pub fn from_name(name: &str) -> Option<ConflictPolicy> {
+        POLICY_NAMES.iter().position(|n| *n == name).map(|i| ConflictPolicy::ALL[i])
+    }
+}
+
+/// How a node of the plan relates to what is on disk.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    New,
+    /// A shared folder that is already there, like the folder of a project type.
+    Exists,
+    /// A folder of this project alone or a file that is already there.
+    Conflict,
+}
+
+pub fn status(path: &Path, unique: bool) -> Status {
+    match (path.exists(), unique) {
+        (false, _) => Status::New,
+        (true, false) => Status::Exists,
+        (true, true) => Status::Conflict,
+    }
+}
+
+/// The paths of the nodes of the plan that conflict with what is on disk.
+///
+/// The root has to be resolved to an absolute path. Below a conflicting folder nothing
+/// is reported, its children are part of the same conflict.
+pub fn find_conflicts(plan: &Tree<BuildNode>) -> Vec<PathBuf> {
+    conflicting_nodes(plan).into_iter().map(|(_, path)| path).collect()
+}
+
+/// Changes the plan so it can be built with `policy`, the paths in the way are returned for
+/// `Abort` and `Prompt` if there are any.
+pub fn apply_policy(plan: &mut Tree<BuildNode>, policy: ConflictPolicy) -> Result<(), Vec<PathBuf>> {
+    let conflicts = conflicting_nodes(plan);
+    if conflicts.is_empty() {
+        return Ok(());
+    }
+    match policy {
+        ConflictPolicy::Abort | ConflictPolicy::Prompt => return Err(conflicts.into_iter().map(|(_, path)| path).collect()),
+        ConflictPolicy::Merge => {
+            // the folders are built into, the files in them are kept
+            let files: Vec<NodeId> = node_paths(plan).into_iter()
+                .filter(|(id, path)| plan.get(*id).is_some_and(|n| n.data().is_file()) && path.exists())
+                .map(|(id, _)| id)
+                .collect();
+            for node_id in files {
+                plan.remove(node_id, RemoveBehavior::DropChildren);
+            }
+        }
+        ConflictPolicy::Suffix => {
+            for (node_id, path) in conflicts {
+                if let Some(mut node) = plan.get_mut(node_id) {
+                    let name = &mut node.data().name;
+                    *name = free_name(&path, name);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn conflicting_nodes(plan: &Tree<BuildNode>) -> Vec<(NodeId, PathBuf)> {
+    fn walk(node: NodeRef<BuildNode>, path: &Path, found: &mut Vec<(NodeId, PathBuf)>) {
+        for child in node.children() {
+            let child_path = path.join(&child.data().name);
+            match status(&child_path, child.data().is_unique()) {
+                Status::New => {}
+                Status::Exists => walk(child, &child_path, found),
+                Status::Conflict => found.push((child.node_id(), child_path)),
+            }
+        }
+    }
+    let mut found = Vec::new();
+    if let Some(root) = plan.root() {
+        let location = PathBuf::from(&root.data().name);
+        walk(root, &location, &mut found);
+    }
+    found
+}
+
+/// Every node of the plan below the root with its path.
+fn node_paths(plan: &Tree<BuildNode>) -> Vec<(NodeId, PathBuf)> {
+    fn walk(node: NodeRef<BuildNode>, path: &Path, found: &mut Vec<(NodeId, PathBuf)>) {
+        for child in node.children() {
+            let child_path = path.join(&child.data().name);
+            found.push((child.node_id(), child_path.clone()));
+            walk(child, &child_path, found);
+        }
+    }
+    let mut found = Vec::new();
+    if let Some(root) = plan.root() {
+        let location = PathBuf::from(&root.data().name);
+        walk(root, &location, &mut found);
+    }
+    found
+}
+
+/// The first of `name_2`, `name_3`, ... that is free next to `path`, files keep their extension.
+fn free_name(path: &Path, name: &str) -> String {
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) if path.is_file() && !stem.is_empty() => (stem, format!(".{}", extension)),
+        _ => (name, String::new()),
+    };
+    (2..)
+        .map(|n| format!("{}_{}{}", stem, n, extension))
+        .find(|candidate| !path.with_file_name(candidate).exists())
+        .expect("a free name")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use slab_tree::TreeBuilder;
+    use crate::backend::tree::nodes::FileContent;
+    use super::*;
+
+    fn plan(location: &Path) -> Tree<BuildNode> {
+        let mut tree = TreeBuilder::new().with_root(BuildNode::folder(location.to_string_lossy())).build();
+        let mut root = tree.root_mut().unwrap();
+        let mut song = root.append(BuildNode::folder("Song"));
+        let mut tune = song.append(BuildNode::folder("Tune").unique(true));
+        tune.append(BuildNode::folder("Stems"));
+        tune.append(BuildNode::file("mix.wav", FileContent::Empty));
+        song.append(BuildNode::file("notes.txt", FileContent::Empty));
+        tree
+    }
+
+    fn names(plan: &Tree<BuildNode>) -> Vec<String> {
+        plan.root().unwrap().traverse_pre_order().skip(1).map(|n| n.data().to_string()).collect()
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let location = std::env::temp_dir().join("scaffolder_conflicts");
+        let _ = fs::remove_dir_all(&location);
+        fs::create_dir_all(location.join("Song")).unwrap();
+        // a shared folder is no conflict
+        assert!(find_conflicts(&plan(&location)).is_empty());
+
+        fs::create_dir_all(location.join("Song/Tune/Stems")).unwrap();
+        fs::create_dir_all(location.join("Song/Tune_2")).unwrap();
+        fs::write(location.join("Song/notes.txt"), "kept").unwrap();
+        fs::write(location.join("Song/Tune/mix.wav"), "kept").unwrap();
+        assert_eq!(find_conflicts(&plan(&location)), vec![location.join("Song/Tune"), location.join("Song/notes.txt")]);
+
+        let mut aborted = plan(&location);
+        assert_eq!(apply_policy(&mut aborted, ConflictPolicy::Abort).unwrap_err().len(), 2);
+
+        let mut merged = plan(&location);
+        apply_policy(&mut merged, ConflictPolicy::Merge).unwrap();
+        assert_eq!(names(&merged), vec!["Song/", "Tune/", "Stems/"]);
+
+        let mut suffixed = plan(&location);
+        apply_policy(&mut suffixed, ConflictPolicy::Suffix).unwrap();
+        assert_eq!(names(&suffixed), vec!["Song/", "Tune_3/", "Stems/", "mix.wav", "notes_2.txt"]);
+        fs::remove_dir_all(location).unwrap();
+    }
+}
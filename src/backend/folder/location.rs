@@ -0,0 +1,176 @@
+use std::env;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Turns the raw `Location` input into an absolute path.
+///
+/// Expands a leading `~` or `~user`, then `$VAR` and `${VAR}` in the rest, and makes
+/// relative paths absolute against the current working directory. A `~` coming from the
+/// value of a variable is kept as it is, like a shell does.
+pub fn resolve_location(raw: &str) -> Result<PathBuf, String> {
+    resolve_location_with(raw, home_dir().as_deref(), |name| env::var(name).ok())
+}
+
+/// [`resolve_location`] with the home directory and the environment variables passed in.
+pub fn resolve_location_with<F>(raw: &str, home: Option<&Path>, var: F) -> Result<PathBuf, String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let (home, rest) = split_home(raw.trim(), home)?;
+    let rest = expand_vars(rest, var)?;
+    let expanded = match home {
+        Some(home) if rest.is_empty() => home,
+        Some(home) => home.join(rest),
+        None => PathBuf::from(rest),
+    };
+    if expanded.as_os_str().is_empty() {
+        return Err(String::from("the location is empty"));
+    }
+
+    let absolute = if expanded.is_absolute() {
+        expanded
+    } else {
+        env::current_dir()
+            .map_err(|e| format!("could not read the current directory: {}", e))?
+            .join(expanded)
+    };
+    Ok(normalize(&absolute))
+}
+
+fn expand_vars<F: Fn(&str) -> Option<String>>(raw: &str, var: F) -> Result<String, String> {
+    let mut expanded = String::new();
+    let mut chars = raw.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            expanded.push(c);
+            continue;
+        }
+        let var_name = if chars.peek() == Some(&'{') {
+            chars.next();
+            let mut name = String::new();
+            loop {
+                match chars.next() {
+                    Some('}') => break,
+                    Some(c) => name.push(c),
+                    None => return Err(format!("missing closing '}}' for variable '${{{}'", name)),
+                }
+            }
+            name
+        } else {
+            let mut name = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_ascii_alphanumeric() || c == '_' {
+                    name.push(c);
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            if name.is_empty() {
+                expanded.push('$');
+                continue;
+            }
+            name
+        };
+        let value = var(&var_name)
+            .ok_or_else(|| format!("environment variable '{}' is not set", var_name))?;
+        expanded.push_str(&value);
+    }
+    Ok(expanded)
+}
+
+/// The home directory a leading `~` or `~user` stands for and the rest of the path after it.
+fn split_home<'a>(path: &'a str, home: Option<&Path>) -> Result<(Option<PathBuf>, &'a str), String> {
+    let Some(rest) = path.strip_prefix('~') else {
+        return Ok((None, path));
+    };
+    let (user, tail) = match rest.find(['/', '\\']) {
+        Some(i) => (&rest[..i], &rest[i + 1..]),
+        None => (rest, ""),
+    };
+    let home = if user.is_empty() {
+        home.map(Path::to_path_buf)
+            .ok_or_else(|| String::from("could not determine the home directory, HOME is not set"))?
+    } else {
+        user_home_dir(user)?
+    };
+    Ok((Some(home), tail))
+}
+
+fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
+fn user_home_dir(user: &str) -> Result<PathBuf, String> {
+    let passwd = fs::read_to_string("/etc/passwd").unwrap_or_default();
+    passwd.lines()
+        .map(|line| line.split(':').collect::<Vec<&str>>())
+        .find(|fields| fields.len() > 5 && fields[0] == user)
+        .map(|fields| PathBuf::from(fields[5]))
+        .ok_or_else(|| format!("unknown user '{}' in '~{}'", user, user))
+}
+
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => { normalized.pop(); }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(name: &str) -> Option<String> {
+        match name {
+            "SCAFFOLDER_TEST_ROOT" => Some(String::from("/srv/projects")),
+            "SCAFFOLDER_TEST_TILDE" => Some(String::from("~/from_var")),
+            _ => None,
+        }
+    }
+
+    fn resolve(raw: &str) -> Result<PathBuf, String> {
+        resolve_location_with(raw, Some(Path::new("/home/tester")), vars)
+    }
+
+    #[test]
+    fn test_expand_home() {
+        assert_eq!(resolve("~/music").unwrap(), PathBuf::from("/home/tester/music"));
+        assert_eq!(resolve("~").unwrap(), PathBuf::from("/home/tester"));
+        assert!(resolve_location_with("~/music", None, vars).is_err());
+    }
+
+    #[test]
+    fn test_expand_env_vars() {
+        assert_eq!(resolve("$SCAFFOLDER_TEST_ROOT/a").unwrap(), PathBuf::from("/srv/projects/a"));
+        assert_eq!(resolve("${SCAFFOLDER_TEST_ROOT}_old/b").unwrap(), PathBuf::from("/srv/projects_old/b"));
+        assert!(resolve("$SCAFFOLDER_TEST_UNSET_VAR/a").is_err());
+        assert!(resolve("${SCAFFOLDER_TEST_ROOT/a").is_err());
+    }
+
+    #[test]
+    fn test_home_before_env_vars() {
+        // a `~` from a variable is not the home directory, the path is relative
+        let cwd = env::current_dir().unwrap();
+        assert_eq!(resolve("$SCAFFOLDER_TEST_TILDE/a").unwrap(), cwd.join("~/from_var/a"));
+    }
+
+    #[test]
+    fn test_relative_location() {
+        let cwd = env::current_dir().unwrap();
+        assert_eq!(resolve("./out/../music").unwrap(), cwd.join("music"));
+    }
+
+    #[test]
+    fn test_unknown_user() {
+        assert!(resolve("~no_such_user_for_scaffolder/music").is_err());
+    }
+}
@@ -1,7 +1,76 @@
-use slab_tree::Tree;
+use std::path::PathBuf;
+use slab_tree::{NodeId, Tree};
+use crate::backend::tree::nodes::{BuildNode, FileContent};
+use crate::error::{Error, Result};
 
 mod builder;
+mod conflict;
+mod location;
+mod plan;
 
-pub fn build_folder_structure(tree: Tree<String>) -> () {
-    builder::build_folder_structure(tree);
-}
\ No newline at end of file
+pub use builder::Rollback;
+pub use conflict::{find_conflicts, status, ConflictPolicy, Status, POLICY_NAMES};
+pub use plan::{Operation, Plan, Step};
+
+/// Plans the build of `tree` and carries it out, see [`execute_plan`].
+pub fn build_folder_structure(tree: Tree<BuildNode>) -> Result<()> {
+    execute_plan(&build_plan(&tree)?)
+}
+
+/// The steps that build `tree`, with what is on disk at each of their paths.
+pub fn build_plan(tree: &Tree<BuildNode>) -> Result<Plan> {
+    plan::build_plan(tree)
+}
+
+/// Carries out the plan, everything created is removed again if a step fails.
+pub fn execute_plan(plan: &Plan) -> Result<()> {
+    builder::execute_plan(plan)
+}
+
+pub fn resolve_location(location: &str) -> Result<PathBuf> {
+    location::resolve_location(location)
+        .map_err(|e| Error::Validation(format!("could not resolve the project location '{}': {}", location, e)))
+}
+
+/// Replaces the location at the root of `tree` with its absolute path and returns it.
+pub fn resolve_root(tree: &mut Tree<BuildNode>) -> Result<PathBuf> {
+    let mut root = tree.root_mut().ok_or_else(|| Error::Validation(String::from("the folder tree is empty")))?;
+    let location = resolve_location(&root.data().name)?;
+    root.data().name = location.to_string_lossy().to_string();
+    Ok(location)
+}
+
+/// Adds a file to the project folder, the first folder of the plan that belongs to this
+/// project alone. Returns false if the plan has none.
+pub fn add_project_file(plan: &mut Tree<BuildNode>, name: &str, content: String) -> bool {
+    match project_folder(plan).and_then(|id| plan.get_mut(id)) {
+        Some(mut folder) => {
+            folder.append(BuildNode::file(name, FileContent::Inline(content)));
+            true
+        }
+        None => false,
+    }
+}
+
+/// Adds the hooks of the template to the project folder, or to the location if the plan has
+/// no folder of its own. They run after everything else is built.
+pub fn add_hooks(plan: &mut Tree<BuildNode>, hooks: &[String]) {
+    let Some(id) = project_folder(plan).or_else(|| plan.root_id()) else { return };
+    let Some(mut folder) = plan.get_mut(id) else { return };
+    for hook in hooks {
+        folder.append(BuildNode::hook(hook));
+    }
+}
+
+fn project_folder(plan: &Tree<BuildNode>) -> Option<NodeId> {
+    plan.root()?.traverse_pre_order()
+        .find(|node| node.data().unique && !node.data().is_file())
+        .map(|node| node.node_id())
+}
+
+/// Makes the plan fit what is already on disk, see [`ConflictPolicy`].
+///
+/// `Prompt` has to be decided before, it stops like `Abort` if anything is in the way.
+pub fn resolve_conflicts(plan: &mut Tree<BuildNode>, policy: ConflictPolicy) -> Result<()> {
+    conflict::apply_policy(plan, policy).map_err(Error::Conflict)
+}
@@ -0,0 +1,265 @@
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use serde::Serialize;
+use slab_tree::{NodeRef, Tree};
+use crate::backend::folder::conflict::{status, Status};
+use crate::backend::tree::nodes::{BuildKind, BuildNode, FileContent};
+use crate::error::{Error, Result};
+
+/// Everything a build does, in the order it is done.
+///
+/// A dry run prints it and the build carries out the same steps, so what is shown is what
+/// happens. Paths are absolute once the root of the tree is resolved.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Plan {
+    pub root: PathBuf,
+    pub steps: Vec<Step>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Step {
+    #[serde(flatten)]
+    pub operation: Operation,
+    /// What is on disk at the path when the plan is made.
+    pub status: Status,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Operation {
+    /// Creates the folder and the folders above it that are missing.
+    Mkdir { path: PathBuf },
+    WriteFile { path: PathBuf, content: String },
+    /// Copies a file, its permissions included.
+    Copy { path: PathBuf, from: PathBuf },
+    Symlink { path: PathBuf, target: PathBuf },
+    /// Sets the unix permissions of the path, e.g. `0o755`.
+    Chmod { path: PathBuf, mode: u32 },
+    /// Runs a shell command in the folder at the path.
+    Hook { path: PathBuf, command: String },
+}
+
+impl Operation {
+    pub fn path(&self) -> &Path {
+        match self {
+            Operation::Mkdir { path }
+            | Operation::WriteFile { path, .. }
+            | Operation::Copy { path, .. }
+            | Operation::Symlink { path, .. }
+            | Operation::Chmod { path, .. }
+            | Operation::Hook { path, .. } => path,
+        }
+    }
+
+    /// Whether finding the path on disk is a conflict, see [`BuildNode::is_unique`].
+    fn status(&self, unique: bool) -> Status {
+        match self {
+            Operation::Chmod { path, .. } => status(path, false),
+            Operation::Hook { .. } => Status::New,
+            Operation::Mkdir { path } => status(path, unique),
+            other => status(other.path(), true),
+        }
+    }
+}
+
+/// Turns the resolved tree into the steps that build it, folders before what is in them and
+/// hooks once everything is built.
+pub fn build_plan(tree: &Tree<BuildNode>) -> Result<Plan> {
+    let root = tree.root().ok_or_else(|| Error::Validation(String::from("the folder tree is empty")))?;
+    fn walk(node: NodeRef<BuildNode>, parent_path: &Path, steps: &mut Vec<Step>, hooks: &mut Vec<Step>) {
+        let path = parent_path.join(&node.data().name);
+        let operation = match &node.data().kind {
+            BuildKind::Folder => Operation::Mkdir { path: path.clone() },
+            BuildKind::File(content) => file_operation(path.clone(), content),
+            BuildKind::Hook(command) => {
+                hooks.push(Step { operation: Operation::Hook { path: parent_path.to_path_buf(), command: command.clone() }, status: Status::New });
+                return;
+            }
+        };
+        let status = operation.status(node.data().unique);
+        steps.push(Step { operation, status });
+        if let Some(mode) = node.data().mode {
+            let operation = Operation::Chmod { path: path.clone(), mode };
+            let status = operation.status(false);
+            steps.push(Step { operation, status });
+        }
+        for child in node.children() {
+            walk(child, &path, steps, hooks);
+        }
+    }
+    let location = PathBuf::from(&root.data().name);
+    let mut steps = Vec::new();
+    let mut hooks = Vec::new();
+    walk(root, &PathBuf::new(), &mut steps, &mut hooks);
+    steps.append(&mut hooks);
+    Ok(Plan { root: location, steps })
+}
+
+fn file_operation(path: PathBuf, content: &FileContent) -> Operation {
+    match content {
+        FileContent::Empty => Operation::WriteFile { path, content: String::new() },
+        FileContent::Inline(text) => Operation::WriteFile { path, content: text.clone() },
+        // copied folders are expanded when the tree is resolved
+        FileContent::Path(from) | FileContent::Copy { from, .. } => Operation::Copy { path, from: from.clone() },
+        FileContent::Symlink(target) => Operation::Symlink { path, target: target.clone() },
+        // and templates are rendered
+        FileContent::Template(content, _) => file_operation(path, content),
+    }
+}
+
+impl Plan {
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// The index of the folder each step is shown in, the closest folder above its path.
+    fn parents(&self) -> Vec<Option<usize>> {
+        let steps = &self.steps;
+        (0..steps.len())
+            .map(|i| {
+                let path = steps[i].operation.path();
+                (0..i).rev().find(|j| match &steps[*j].operation {
+                    // hooks run in the folder at their path
+                    Operation::Mkdir { path: folder } => match steps[i].operation {
+                        Operation::Hook { .. } => path.starts_with(folder),
+                        _ => path != folder && path.starts_with(folder),
+                    },
+                    _ => false,
+                })
+            })
+            .collect()
+    }
+
+    fn label(&self, i: usize, parent: Option<usize>) -> String {
+        let step = &self.steps[i];
+        let path = step.operation.path();
+        let name = match parent.map(|p| self.steps[p].operation.path()) {
+            Some(folder) => path.strip_prefix(folder).unwrap_or(path).display().to_string(),
+            None => path.display().to_string(),
+        };
+        let label = match &step.operation {
+            Operation::Mkdir { .. } => format!("{}/", name),
+            Operation::WriteFile { .. } => name,
+            Operation::Copy { from, .. } => format!("{} (copy of {})", name, from.display()),
+            Operation::Symlink { target, .. } => format!("{} -> {}", name, target.display()),
+            Operation::Chmod { mode, .. } => format!("chmod {:o} {}", mode, name),
+            Operation::Hook { command, .. } => format!("$ {}", command),
+        };
+        match step.status {
+            Status::New => label,
+            Status::Exists => format!("{} [exists]", label),
+            Status::Conflict => format!("{} [conflict]", label),
+        }
+    }
+}
+
+/// Shows the steps as a tree of folders.
+impl Display for Plan {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let parents = self.parents();
+        fn subtree(plan: &Plan, parents: &[Option<usize>], i: usize) -> termtree::Tree<String> {
+            let children = (i + 1..parents.len())
+                .filter(|j| parents[*j] == Some(i))
+                .map(|j| subtree(plan, parents, j));
+            termtree::Tree::new(plan.label(i, parents[i])).with_leaves(children)
+        }
+        for i in (0..parents.len()).filter(|i| parents[*i].is_none()) {
+            write!(f, "{}", subtree(self, &parents, i))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use slab_tree::TreeBuilder;
+    use crate::backend::folder::add_project_file;
+    use super::*;
+
+    #[test]
+    fn test_build_plan() {
+        let location = std::env::temp_dir().join("scaffolder_plan");
+        let _ = fs::remove_dir_all(&location);
+        fs::create_dir_all(location.join("Song/Tune")).unwrap();
+
+        let mut tree = TreeBuilder::new().with_root(BuildNode::folder(location.to_string_lossy())).build();
+        let mut root = tree.root_mut().unwrap();
+        let mut song = root.append(BuildNode::folder("Song"));
+        let mut tune = song.append(BuildNode::folder("Tune").unique(true));
+        tune.append(BuildNode::file("notes.txt", FileContent::Inline(String::from("notes"))));
+        song.append(BuildNode::file("Takes/mix.wav", FileContent::Path(PathBuf::from("/assets/mix.wav"))));
+
+        let plan = build_plan(&tree).unwrap();
+        let steps: Vec<(&Path, Status)> = plan.steps.iter().map(|s| (s.operation.path(), s.status)).collect();
+        assert_eq!(steps, vec![
+            (location.as_path(), Status::Exists),
+            (location.join("Song").as_path(), Status::Exists),
+            (location.join("Song/Tune").as_path(), Status::Conflict),
+            (location.join("Song/Tune/notes.txt").as_path(), Status::New),
+            (location.join("Song/Takes/mix.wav").as_path(), Status::New),
+        ]);
+        assert_eq!(plan.to_string(), format!("\
+{}/ [exists]
+└── Song/ [exists]
+    ├── Tune/ [conflict]
+    │   └── notes.txt
+    └── Takes/mix.wav (copy of /assets/mix.wav)
+", location.display()));
+
+        let json: serde_json::Value = serde_json::from_str(&plan.to_json().unwrap()).unwrap();
+        assert_eq!(json["steps"][3], serde_json::json!({
+            "op": "write_file",
+            "path": location.join("Song/Tune/notes.txt"),
+            "content": "notes",
+            "status": "new",
+        }));
+        fs::remove_dir_all(location).unwrap();
+    }
+
+    #[test]
+    fn test_links_modes_and_hooks() {
+        let location = std::env::temp_dir().join("scaffolder_plan_hooks");
+        let mut tree = TreeBuilder::new().with_root(BuildNode::folder(location.to_string_lossy())).build();
+        let mut root = tree.root_mut().unwrap();
+        let mut tune = root.append(BuildNode::folder("Tune").unique(true));
+        tune.append(BuildNode::hook("git init"));
+        tune.append(BuildNode::file("run.sh", FileContent::Empty).mode(Some(0o755)));
+        tune.append(BuildNode::file("latest", FileContent::Symlink(PathBuf::from("Tune.als"))));
+        root.append(BuildNode::folder("Shared"));
+
+        // hooks run last, in the folder they belong to
+        let plan = build_plan(&tree).unwrap();
+        assert_eq!(plan.to_string(), format!("\
+{}/
+├── Tune/
+│   ├── run.sh
+│   ├── chmod 755 run.sh
+│   ├── latest -> Tune.als
+│   └── $ git init
+└── Shared/
+", location.display()));
+        let json: serde_json::Value = serde_json::from_str(&plan.to_json().unwrap()).unwrap();
+        assert_eq!(json["steps"][3], serde_json::json!({ "op": "chmod", "path": location.join("Tune/run.sh"), "mode": 0o755, "status": "new" }));
+        assert_eq!(json["steps"][6], serde_json::json!({ "op": "hook", "path": location.join("Tune"), "command": "git init", "status": "new" }));
+    }
+
+    #[test]
+    fn test_project_file() {
+        let location = std::env::temp_dir().join("scaffolder_project_file");
+        let mut tree = TreeBuilder::new().with_root(BuildNode::folder(location.to_string_lossy())).build();
+        assert!(!add_project_file(&mut tree, ".answers.yaml", String::new()));
+
+        let mut root = tree.root_mut().unwrap();
+        let mut beat = root.append(BuildNode::folder("Beat"));
+        beat.append(BuildNode::folder("Tune").unique(true)).append(BuildNode::folder("Stems"));
+        assert!(add_project_file(&mut tree, ".answers.yaml", String::from("Name: Tune\n")));
+
+        // the file is part of the plan, next to what else is in the project folder
+        let plan = build_plan(&tree).unwrap();
+        assert_eq!(plan.steps.last().unwrap().operation, Operation::WriteFile {
+            path: location.join("Beat/Tune/.answers.yaml"),
+            content: String::from("Name: Tune\n"),
+        });
+    }
+}
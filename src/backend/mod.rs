@@ -1,3 +1,4 @@
+pub mod answers;
 pub mod yaml;
 pub mod tree;
 pub mod folder;
\ No newline at end of file
@@ -0,0 +1,89 @@
+use slab_tree::{NodeId, Tree};
+use crate::backend::tree::nodes::{LeafNodeType, NodePath};
+use crate::backend::tree::scope::{answers, Scope};
+
+/// Limits a node to some answers of other nodes, e.g. `when: {ProjectType: Song}`.
+///
+/// Every entry names a node the same way answers do and lists the answers it may have,
+/// all entries have to match.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Condition(pub Vec<(String, Vec<String>)>);
+
+/// Whether the nodes of a tree are part of the project with the answers given so far.
+pub trait NodeEnabled {
+    /// False if the node or one of its ancestors has a `when` that does not match.
+    fn is_enabled(&self, node_id: NodeId) -> bool;
+    /// Like [`is_enabled`](NodeEnabled::is_enabled) inside one copy of a multi-select branch.
+    fn is_enabled_in(&self, node_id: NodeId, scope: &Scope) -> bool;
+}
+
+impl NodeEnabled for Tree<LeafNodeType> {
+    fn is_enabled(&self, node_id: NodeId) -> bool {
+        self.is_enabled_in(node_id, &Scope::default())
+    }
+
+    fn is_enabled_in(&self, node_id: NodeId, scope: &Scope) -> bool {
+        enabled(self, node_id, scope, &mut Vec::new())
+    }
+}
+
+fn enabled(tree: &Tree<LeafNodeType>, node_id: NodeId, scope: &Scope, stack: &mut Vec<NodeId>) -> bool {
+    // a condition that depends on its own node can never be met
+    if stack.contains(&node_id) {
+        return false;
+    }
+    let Some(node) = tree.get(node_id) else { return false };
+    let mut conditions: Vec<&Condition> = node.data().when().into_iter().collect();
+    conditions.extend(node.ancestors().filter_map(|a| a.data().when()));
+
+    stack.push(node_id);
+    let enabled = conditions.iter()
+        .flat_map(|condition| condition.0.iter())
+        .all(|(key, values)| matches(tree, key, values, scope, stack));
+    stack.pop();
+    enabled
+}
+
+/// A multi-select option matches if one of its checked items is in `values`.
+fn matches(tree: &Tree<LeafNodeType>, key: &str, values: &[String], scope: &Scope, stack: &mut Vec<NodeId>) -> bool {
+    let Some(target) = tree.find_node(key) else { return false };
+    if !enabled(tree, target, scope, stack) {
+        return false;
+    }
+    answers(tree, target, scope).iter().any(|answer| values.contains(answer))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use crate::backend::answers::{apply_answer, Expect};
+    use crate::backend::tree::resolve::resolve_tree;
+    use crate::backend::yaml::load_yaml;
+    use super::*;
+
+    fn conditions(answers: &[(&str, &str)]) -> Tree<LeafNodeType> {
+        let mut tree = load_yaml(Path::new("test/conditions.yaml")).unwrap().0;
+        for (key, value) in answers {
+            apply_answer(&mut tree, key, value, Expect::Any).unwrap();
+        }
+        tree
+    }
+
+    #[test]
+    fn test_conditions() {
+        let names = |tree: Tree<LeafNodeType>| -> Vec<String> {
+            let plan = resolve_tree(&tree).unwrap();
+            let names = plan.root().unwrap().traverse_pre_order().map(|n| n.data().name.clone()).collect();
+            names
+        };
+
+        // the disabled `Ableton` branch does not need an answer for `SetName`
+        let beat = conditions(&[("ProjectType", "Beat"), ("DAW", "Fl Studio")]);
+        assert_eq!(names(beat), vec!["/tmp/scaffolder-conditions", "Beat", "Fl Studio"]);
+
+        let err = resolve_tree(&conditions(&[("ProjectType", "Song"), ("DAW", "Ableton")])).unwrap_err();
+        assert_eq!(err.to_string(), "Missing answers for ProjectType/Ableton/SetName");
+        let song = conditions(&[("ProjectType", "Song"), ("DAW", "Ableton"), ("SetName", "Live")]);
+        assert_eq!(names(song), vec!["/tmp/scaffolder-conditions", "Song", "Ableton", "Stems", "Ableton", "Samples", "Live"]);
+    }
+}
@@ -1 +1,10 @@
+pub mod condition;
 pub mod nodes;
+pub mod options;
+pub mod render;
+pub mod resolve;
+pub mod rules;
+pub mod scope;
+pub mod substitute;
+pub mod tokens;
+pub mod transform;
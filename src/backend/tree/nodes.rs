@@ -1,11 +1,116 @@
 use std::fmt::{Debug, Display};
+use std::path::PathBuf;
 use slab_tree::{NodeId, NodeMut, NodeRef, Tree};
-use crate::front_end::ui::ui::StatefulList;
+use crate::backend::tree::condition::{Condition, NodeEnabled};
+use crate::backend::tree::options::StatefulList;
+use crate::backend::tree::rules::Rules;
+use crate::backend::tree::transform::Transform;
 
+/// A node of the question tree, `when` limits it and its children to some answers.
+#[derive(Clone)]
 pub enum LeafNodeType {
-    Text { name: String },
-    Option { options: StatefulList, name: String },
-    TextInput { name: String, input: String },
+    Text { name: String, when: Option<Condition> },
+    Option { options: StatefulList, name: String, when: Option<Condition> },
+    TextInput { name: String, input: String, rules: Rules, transform: Vec<Transform>, when: Option<Condition> },
+    /// `mode` are the unix permissions the file gets once it is written, e.g. `0o755`.
+    File { name: String, content: FileContent, mode: Option<u32>, when: Option<Condition> },
+}
+
+/// What a file node is filled with when it is built.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FileContent {
+    Empty,
+    Inline(String),
+    /// A file next to the template whose content is copied, the path is absolute.
+    Path(PathBuf),
+    /// A file or a folder next to the template that is copied with everything in it, the path
+    /// is absolute. The placeholders in the copied names are replaced with `render_names`.
+    Copy { from: PathBuf, render_names: bool },
+    /// Inline content or a content file that is rendered with the answers when the tree is
+    /// resolved, see [`render`](crate::backend::tree::render::render). Inline content knows
+    /// where it starts in the template, so render errors can point there.
+    Template(Box<FileContent>, Option<SourceLine>),
+    /// A link to the path, a relative path is relative to the folder the link is in.
+    Symlink(PathBuf),
+}
+
+/// A line in one of the files a template was read from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SourceLine {
+    pub file: String,
+    pub line: usize,
+}
+
+/// An entry of the resolved tree that gets built on disk.
+#[derive(Clone, PartialEq)]
+pub struct BuildNode {
+    pub name: String,
+    pub kind: BuildKind,
+    /// The folder is named by a text input, so it belongs to this project alone and finding
+    /// it on disk is a conflict. Files are always their own.
+    pub unique: bool,
+    /// The unix permissions of a file, they are set after it is written.
+    pub mode: Option<u32>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BuildKind {
+    Folder,
+    File(FileContent),
+    /// A shell command run in the folder of the parent once everything else is built.
+    Hook(String),
+}
+
+impl BuildNode {
+    pub fn folder(name: impl Into<String>) -> BuildNode {
+        BuildNode { name: name.into(), kind: BuildKind::Folder, unique: false, mode: None }
+    }
+
+    pub fn file(name: impl Into<String>, content: FileContent) -> BuildNode {
+        BuildNode { name: name.into(), kind: BuildKind::File(content), unique: false, mode: None }
+    }
+
+    /// Hooks have no name, they run in the folder of their parent.
+    pub fn hook(command: impl Into<String>) -> BuildNode {
+        BuildNode { name: String::new(), kind: BuildKind::Hook(command.into()), unique: false, mode: None }
+    }
+
+    pub fn unique(mut self, unique: bool) -> BuildNode {
+        self.unique = unique;
+        self
+    }
+
+    pub fn mode(mut self, mode: Option<u32>) -> BuildNode {
+        self.mode = mode;
+        self
+    }
+
+    pub fn is_file(&self) -> bool {
+        matches!(self.kind, BuildKind::File(_))
+    }
+
+    /// Whether finding the node on disk is a conflict.
+    pub fn is_unique(&self) -> bool {
+        self.unique || self.is_file()
+    }
+}
+
+impl Display for BuildNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.kind {
+            BuildKind::Folder => write!(f, "{}/", self.name),
+            BuildKind::File(FileContent::Symlink(target)) => write!(f, "{} -> {}", self.name, target.display()),
+            BuildKind::File(_) => write!(f, "{}", self.name),
+            BuildKind::Hook(command) => write!(f, "$ {}", command),
+        }
+    }
+}
+
+// the dry run prints the tree with `write_formatted`, which uses Debug
+impl Debug for BuildNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
 }
 
 pub trait CloneTree {
@@ -35,56 +140,116 @@ pub trait NodeIndex{
 
 impl NodeIndex for Tree<LeafNodeType> {
     fn node_index(&self, node_id: NodeId) -> usize {
-        fn index(node: NodeRef<LeafNodeType>, vertical_index: &mut usize, node_id: NodeId, recursive_break:  &mut bool) {
+        // disabled branches are not shown, so they are not counted
+        fn index(tree: &Tree<LeafNodeType>, node: NodeRef<LeafNodeType>, vertical_index: &mut usize, node_id: NodeId, recursive_break:  &mut bool) {
 
-            for child in node.children() {
+            for child in node.children().filter(|c| tree.is_enabled(c.node_id())) {
                 if child.node_id() == node_id{
                     *recursive_break = true;
                     *vertical_index += 1;
                     return;
                 } else if *recursive_break { return; }
                 *vertical_index += 1;
-                index(child, vertical_index, node_id, recursive_break);
+                index(tree, child, vertical_index, node_id, recursive_break);
             }
         }
         let mut recursive_break = false;
         let mut vertical_index = 0;
         let root = self.root().expect("indexing of tree node failed, Tree has no root");
         if root.node_id() != node_id {
-            index(root, &mut vertical_index, node_id, &mut recursive_break);
+            index(self, root, &mut vertical_index, node_id, &mut recursive_break);
         }
 
         vertical_index
     }
 }
 
+/// Addresses question nodes by their path of template names, e.g. `ProjectType/ProjectName`.
+///
+/// The root is addressed by its own name (`Location`), the path of every other node
+/// starts below the root.
+pub trait NodePath {
+    fn node_path(&self, node_id: NodeId) -> String;
+    /// All nodes whose path is `key` or ends with `/key`.
+    fn find_nodes(&self, key: &str) -> Vec<NodeId>;
+    /// The node whose path is `key`, or else the first one whose path ends with `/key`.
+    fn find_node(&self, key: &str) -> Option<NodeId>;
+}
+
+impl NodePath for Tree<LeafNodeType> {
+    fn node_path(&self, node_id: NodeId) -> String {
+        let Some(node) = self.get(node_id) else { return String::new() };
+        let mut names = vec![node.data().get_name()];
+        names.extend(node.ancestors().map(|a| a.data().get_name()));
+        if names.len() > 1 {
+            // the root is not part of the path of its descendants
+            names.pop();
+        }
+        names.reverse();
+        names.join("/")
+    }
+
+    fn find_nodes(&self, key: &str) -> Vec<NodeId> {
+        match self.root() {
+            Some(root) => root.traverse_pre_order()
+                .map(|node| node.node_id())
+                .filter(|id| path_matches(&self.node_path(*id), key))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    fn find_node(&self, key: &str) -> Option<NodeId> {
+        let found = self.find_nodes(key);
+        let key = key.trim_matches('/');
+        found.iter().find(|id| self.node_path(**id) == key).or(found.first()).copied()
+    }
+}
+
+/// Whether the node at `path` is addressed by `key`, its whole path or the end of it.
+pub fn path_matches(path: &str, key: &str) -> bool {
+    let key = key.trim_matches('/');
+    path == key || path.strip_suffix(key).is_some_and(|rest| rest.ends_with('/'))
+}
+
 impl LeafNodeType {
     pub fn get_name(&self) -> &str {
         match self {
-            LeafNodeType::Text { name } => { name }
+            LeafNodeType::Text { name, .. } => { name }
             LeafNodeType::Option { name, .. } => { name }
             LeafNodeType::TextInput { name, .. } => { name }
+            LeafNodeType::File { name, .. } => { name }
         }
     }
-    pub fn clone(&self) -> LeafNodeType {
+
+    pub fn when(&self) -> Option<&Condition> {
         match self {
-            LeafNodeType::Option {name, options} => LeafNodeType::Option {name: name.clone(), options: options.clone()},
-            LeafNodeType::TextInput {name, input} => LeafNodeType::TextInput{name: name.clone(), input: input.clone()},
-            LeafNodeType::Text {name} => return LeafNodeType::Text {name: name.clone()},
+            LeafNodeType::Text { when, .. }
+            | LeafNodeType::Option { when, .. }
+            | LeafNodeType::TextInput { when, .. }
+            | LeafNodeType::File { when, .. } => when.as_ref(),
         }
     }
+
+    /// Folders and files with a fixed name, they ask no question.
+    pub fn is_fixed(&self) -> bool {
+        matches!(self, LeafNodeType::Text { .. } | LeafNodeType::File { .. })
+    }
 }
 
 
 impl Debug for LeafNodeType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            LeafNodeType::Text { name } => write!(f, "{}", name),
-            LeafNodeType::Option { name, options } => {
+            LeafNodeType::Text { name, .. } => write!(f, "{}", name),
+            LeafNodeType::Option { name, options, .. } => {
                 let options_as_string: String = options.join_names_with(", ");
                 write!(f, "{:?} with [{}]", name, options_as_string)
             }
-            LeafNodeType::TextInput { name, input } => write!(f, "{:?} input: {}", name, input),
+            LeafNodeType::TextInput { name, input, .. } => write!(f, "{:?} input: {}", name, input),
+            LeafNodeType::File { name, content: FileContent::Copy { .. }, .. } => write!(f, "{} (copy)", name),
+            LeafNodeType::File { name, content: FileContent::Symlink(_), .. } => write!(f, "{} (link)", name),
+            LeafNodeType::File { name, .. } => write!(f, "{} (file)", name),
             // Add formatting for additional variants
         }
     }
@@ -93,12 +258,15 @@ impl Debug for LeafNodeType {
 impl Display for LeafNodeType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            LeafNodeType::Text { name } => write!(f, "{}", name),
-            LeafNodeType::Option { name, options } => {
+            LeafNodeType::Text { name, .. } => write!(f, "{}", name),
+            LeafNodeType::Option { name, options, .. } => {
                 let options_as_string: String = options.join_names_with(", ");
                 write!(f, "{} with [{}]", name, options_as_string)
             }
-            LeafNodeType::TextInput { name, input } => write!(f, "{} input: {}", name, input),
+            LeafNodeType::TextInput { name, input, .. } => write!(f, "{} input: {}", name, input),
+            LeafNodeType::File { name, content: FileContent::Copy { .. }, .. } => write!(f, "{} (copy)", name),
+            LeafNodeType::File { name, content: FileContent::Symlink(_), .. } => write!(f, "{} (link)", name),
+            LeafNodeType::File { name, .. } => write!(f, "{} (file)", name),
             // Add formatting for additional variants
         }
     }
@@ -0,0 +1,100 @@
+/// The choices of an `Option` node, the cursor and which items are checked.
+#[derive(Clone)]
+pub struct StatefulList {
+    cursor: Option<usize>,
+    items: Vec<(String, bool)>,
+    multiselect: bool,
+}
+impl StatefulList {
+    pub fn with_items(items: Vec<(String, bool)>, multiselect: bool) -> StatefulList {
+        StatefulList {
+            cursor: Some(0),
+            items,
+            multiselect,
+        }
+    }
+    pub fn join_names_with(&self, separator: &str) -> String {
+        let vec: Vec<String> = self.items.iter().map(|(name, _)| name.clone()).collect();
+        vec.join(separator)
+    }
+    pub fn items(&self) -> &[(String, bool)] {
+        &self.items
+    }
+    pub fn cursor(&self) -> Option<usize> {
+        self.cursor
+    }
+    pub fn is_multiselect(&self) -> bool {
+        self.multiselect
+    }
+    pub fn next(&mut self) {
+        let i = match self.cursor {
+            Some(i) => (i + 1) % self.items.len(),
+            None => 0,
+        };
+        self.cursor = Some(i);
+    }
+    pub fn previous(&mut self) {
+        let i = match self.cursor {
+            Some(i) => {
+                if i == 0 {
+                    self.items.len() - 1
+                } else {
+                    i - 1
+                }
+            }
+            None => 0,
+        };
+        self.cursor = Some(i);
+    }
+    pub fn mark_item(&mut self) -> Option<String> {
+        let item_pos = self.cursor;
+        match item_pos {
+            Some(_) => {
+                //if not multiselect remove clear selection
+                if !self.multiselect {
+                    for i in self.items.iter_mut() { i.1 = false; }
+                }
+                if let Some(pos) = item_pos {
+                    //mark current item
+                    self.items[pos].1 = !self.items[pos].1;
+                    return None;
+                }
+
+                Some(String::from("invalid selection"))
+
+            }
+            None => { Some(String::from("please make a selection")) }
+        }
+    }
+    /// Moves the cursor to `item` and checks it, returns false if there is no such item.
+    pub fn select(&mut self, item: &str) -> bool {
+        match self.items.iter().position(|(name, _)| name == item) {
+            Some(pos) => {
+                self.cursor = Some(pos);
+                if !self.items[pos].1 {
+                    self.mark_item();
+                }
+                true
+            }
+            None => false,
+        }
+    }
+    /// Unchecks every item.
+    pub fn clear(&mut self) {
+        for item in self.items.iter_mut() {
+            item.1 = false;
+        }
+    }
+    pub fn get_selected(&self) -> Option<String> {
+        for item in self.items.clone() {
+            if item.1 {
+                return Some(item.0)
+            }
+        }
+        None
+    }
+    /// All checked items, in the order of the list.
+    pub fn selected(&self) -> Vec<String> {
+        self.items.iter().filter(|(_, checked)| *checked).map(|(name, _)| name.clone()).collect()
+    }
+}
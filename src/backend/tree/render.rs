@@ -0,0 +1,226 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use minijinja::{Environment, UndefinedBehavior, Value};
+use slab_tree::Tree;
+use crate::backend::tree::condition::NodeEnabled;
+use crate::backend::tree::nodes::LeafNodeType;
+use crate::backend::tree::scope::{answers, Scope};
+use crate::backend::tree::substitute::resolved_answer_in;
+use crate::backend::tree::tokens::{Token, DATE_FORMAT, TIME_FORMAT};
+use crate::backend::tree::transform::{Transform, TRANSFORM_NAMES};
+
+/// Why a file template could not be rendered, `line` is counted from the start of the template.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TemplateError {
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl Display for TemplateError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl From<minijinja::Error> for TemplateError {
+    fn from(e: minijinja::Error) -> Self {
+        let message = match e.detail() {
+            Some(detail) => format!("{}: {}", e.kind(), detail),
+            None => e.kind().to_string(),
+        };
+        TemplateError { line: e.line(), message }
+    }
+}
+
+/// Renders the content of a file with the answers of the tree.
+///
+/// Every text input and option is a variable named like its node, a multi-select option is
+/// the list of its checked items (in the folder of one checked item only that one) and a
+/// question that is not part of the project is `none`.
+/// `answers` holds them all for names that are no identifiers, e.g. `answers["Project Type"]`.
+/// `date`, `time`, `user` and `hostname` are there unless a node has the name, and the
+/// transforms of text inputs can be used as filters, e.g. `{{ ProjectName | snake_case }}`.
+pub fn render(tree: &Tree<LeafNodeType>, scope: &Scope, source: &str) -> Result<String, TemplateError> {
+    let environment = environment();
+    let template = environment.template_from_str(source)?;
+    Ok(template.render(context(tree, scope))?)
+}
+
+/// Finds syntax errors without rendering, the variables are only known once the questions
+/// are answered.
+pub fn check_syntax(source: &str) -> Result<(), TemplateError> {
+    environment().template_from_str(source)?;
+    Ok(())
+}
+
+fn environment() -> Environment<'static> {
+    let mut environment = Environment::new();
+    // a typo in a name should not quietly render as nothing
+    environment.set_undefined_behavior(UndefinedBehavior::Strict);
+    // block tags on a line of their own leave no empty line behind
+    environment.set_trim_blocks(true);
+    environment.set_keep_trailing_newline(true);
+    for (name, transform) in TRANSFORM_NAMES.iter().zip(Transform::ALL) {
+        environment.add_filter(name.replace('-', "_"), move |text: String| transform.apply(&text));
+    }
+    environment
+}
+
+fn context(tree: &Tree<LeafNodeType>, scope: &Scope) -> Value {
+    let mut variables: BTreeMap<String, Value> = BTreeMap::new();
+    if let Some(root) = tree.root() {
+        for node in root.traverse_pre_order() {
+            let value = match node.data() {
+                _ if !tree.is_enabled_in(node.node_id(), scope) => Value::from(()),
+                LeafNodeType::Option { options, .. } if options.is_multiselect() => Value::from(answers(tree, node.node_id(), scope)),
+                LeafNodeType::Option { .. } | LeafNodeType::TextInput { .. } => {
+                    resolved_answer_in(tree, node.node_id(), scope).ok().flatten().map_or(Value::from(()), Value::from)
+                }
+                _ => continue,
+            };
+            // the first node with a name is the one placeholders use as well
+            variables.entry(node.data().get_name().to_string()).or_insert(value);
+        }
+    }
+
+    let mut context = BTreeMap::new();
+    let builtins = [
+        ("date", Token::Date(DATE_FORMAT.to_string())),
+        ("time", Token::Time(TIME_FORMAT.to_string())),
+        ("user", Token::User),
+        ("hostname", Token::Hostname),
+    ];
+    for (name, token) in builtins {
+        if let Ok(value) = token.value() {
+            context.insert(name.to_string(), Value::from(value));
+        }
+    }
+    context.insert(String::from("answers"), Value::from(variables.clone()));
+    context.extend(variables);
+    Value::from(context)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use crate::backend::answers::{apply_answer, Expect};
+    use crate::backend::folder::{build_plan, execute_plan, resolve_root};
+    use crate::backend::tree::resolve::resolve_tree;
+    use crate::backend::yaml::{load_yaml, load_yaml_str};
+    use slab_tree::TreeBuilder;
+    use crate::backend::tree::options::StatefulList;
+    use crate::backend::tree::rules::Rules;
+    use super::*;
+
+    fn tree() -> Tree<LeafNodeType> {
+        let mut tree = TreeBuilder::new().with_root(LeafNodeType::Text { name: String::from("Location"), when: None }).build();
+        let mut root = tree.root_mut().unwrap();
+        root.append(LeafNodeType::TextInput {
+            name: String::from("ProjectName"),
+            input: String::from("My Song"),
+            rules: Rules::default(),
+            transform: Vec::new(),
+            when: None,
+        });
+        let items = vec![(String::from("Drums"), true), (String::from("Bass"), false), (String::from("Keys"), true)];
+        root.append(LeafNodeType::Option {
+            options: StatefulList::with_items(items, true),
+            name: String::from("Instruments"),
+            when: None,
+        });
+        tree
+    }
+
+    #[test]
+    fn test_render() {
+        let source = "\
+# {{ ProjectName }} ({{ ProjectName | kebab_case }})
+{% for instrument in Instruments %}
+- {{ instrument | upper }}
+{% endfor %}
+{% if \"Bass\" in Instruments %}bass{% else %}no bass{% endif %}
+
+";
+        let rendered = render(&tree(), &Scope::default(), source).unwrap();
+        assert_eq!(rendered, "# My Song (my-song)\n- DRUMS\n- KEYS\nno bass\n");
+        assert_eq!(render(&tree(), &Scope::default(), "{{ answers[\"ProjectName\"] }}").unwrap(), "My Song");
+        assert!(render(&tree(), &Scope::default(), "{{ date }}").unwrap().len() == 10);
+    }
+
+    #[test]
+    fn test_render_errors() {
+        let err = render(&tree(), &Scope::default(), "# Title\n\n{{ ProjectNam }}\n").unwrap_err();
+        assert_eq!(err.line, Some(3));
+        assert!(err.to_string().starts_with("line 3: undefined value"), "{}", err);
+
+        let err = check_syntax("# Title\n{{ ProjectName }\n").unwrap_err();
+        assert_eq!(err.line, Some(2));
+        assert!(check_syntax("{{ ProjectName }}").is_ok());
+    }
+
+    #[test]
+    fn test_file_templates() {
+        let location = std::env::temp_dir().join("scaffolder_file_templates");
+        let _ = fs::remove_dir_all(&location);
+        let mut tree = load_yaml(Path::new("test/render.yaml")).unwrap().0;
+        apply_answer(&mut tree, "Location", &location.to_string_lossy(), Expect::Text).unwrap();
+        apply_answer(&mut tree, "ProjectName", "My Song", Expect::Text).unwrap();
+        apply_answer(&mut tree, "Instruments", "Drums", Expect::Select).unwrap();
+        apply_answer(&mut tree, "Instruments", "Keys", Expect::Select).unwrap();
+        let mut plan = resolve_tree(&tree).unwrap();
+        let root = resolve_root(&mut plan).unwrap();
+        execute_plan(&build_plan(&plan).unwrap()).unwrap();
+
+        let project = root.join("My Song");
+        assert_eq!(fs::read_to_string(project.join("README.md")).unwrap(), "# My Song\nFolder `my_song`\n");
+        let notes = fs::read_to_string(project.join("session-notes.md")).unwrap();
+        assert!(notes.starts_with("# My Song session notes\n"), "{}", notes);
+        assert!(notes.ends_with("## Instruments\n- Drums\n- Keys\n"), "{}", notes);
+        let metadata = fs::read_to_string(project.join("metadata.json")).unwrap();
+        assert_eq!(metadata, "{\"name\": \"My Song\", \"instruments\": [\"Drums\",\"Keys\"]}\n");
+        fs::remove_dir_all(location).unwrap();
+    }
+
+    #[test]
+    fn test_file_template_errors() {
+        // errors name the file and the line of the template
+        let template = "\
+project:
+  default_location: /tmp
+  children:
+    - notes.md:
+        file:
+          path: files/session-notes.md
+          render: true
+";
+        let tree = load_yaml_str(template, "test/t.yaml").unwrap().0;
+        let err = resolve_tree(&tree).unwrap_err().to_string();
+        assert!(err.starts_with("1 file template(s) could not be rendered\n"), "{}", err);
+        assert!(err.contains("test/files/session-notes.md:1: undefined value"), "{}", err);
+
+        // inline content points at its line in the template file
+        let template = "\
+project:
+  default_location: /tmp
+  children:
+    - README.md:
+        file:
+          content: |
+            # Notes
+            {{ missing.name }}
+          render: true
+    - notes.md:
+        file:
+          content: \"{{ other.name }}\"
+          render: true
+";
+        let tree = load_yaml_str(template, "test/t.yaml").unwrap().0;
+        let err = resolve_tree(&tree).unwrap_err().to_string();
+        assert!(err.contains("test/t.yaml:8: undefined value"), "{}", err);
+        assert!(err.contains("test/t.yaml:12: undefined value"), "{}", err);
+    }
+}
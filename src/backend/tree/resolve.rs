@@ -0,0 +1,333 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use slab_tree::{NodeId, NodeMut, NodeRef, Tree, TreeBuilder};
+use crate::backend::tree::nodes::{BuildNode, FileContent, LeafNodeType, NodePath, SourceLine};
+use crate::backend::tree::render::render;
+use crate::backend::tree::scope::{copies, Scope};
+use crate::backend::tree::substitute::{resolved_answer_in, substitute_in, SubstituteError};
+use crate::backend::tree::transform::transform;
+use crate::error::{Error, Result};
+
+/// Turns the answered question tree into the tree of folders and files to build.
+///
+/// Text inputs become their input and options their selected item, `{{Name}}` placeholders
+/// are replaced with the answer of the named node and branches whose `when` does not match
+/// are left out. A multi-select option becomes one folder per checked item, each with its
+/// own copy of the children. A copied folder becomes a folder with a copy of each file in
+/// it and file templates are rendered with the answers. Questions without an answer, answers
+/// that break the `rules` of their text input, broken references, assets that cannot be read
+/// and templates that do not render are collected and reported together.
+pub fn resolve_tree(tree: &Tree<LeafNodeType>) -> Result<Tree<BuildNode>> {
+    let root = tree.root().ok_or_else(|| Error::Validation(String::from("the template is empty")))?;
+    let mut problems = Problems::default();
+    let root_node = build_node_of(tree, &root, &Scope::default(), &mut problems);
+
+    let mut build_tree: Tree<BuildNode> = TreeBuilder::new().with_root(root_node).build();
+    fn walk_tree(tree: &Tree<LeafNodeType>, node: NodeRef<LeafNodeType>, mut output_node: NodeMut<BuildNode>, scope: &Scope, problems: &mut Problems) {
+        for child in node.children() {
+            for child_scope in copies(tree, child.node_id(), scope) {
+                let build_node = build_node_of(tree, &child, &child_scope, problems);
+                let mut output_child = output_node.append(build_node);
+                if let LeafNodeType::File { content: FileContent::Copy { from, render_names }, .. } = child.data() {
+                    if from.is_dir() {
+                        let copy = Copy { tree, node_id: child.node_id(), scope: &child_scope, render_names: *render_names };
+                        copy.entries(from, &mut output_child, problems);
+                    }
+                }
+                walk_tree(tree, tree.get(child.node_id()).expect("child of the tree"), output_child, &child_scope, problems);
+            }
+        }
+    }
+    if let Some(build_root) = build_tree.root_mut() {
+        walk_tree(tree, root, build_root, &Scope::default(), &mut problems);
+    }
+
+    let mut lines = Vec::new();
+    if !problems.missing.is_empty() {
+        let paths: Vec<String> = problems.missing.into_iter().map(|id| tree.node_path(id)).collect();
+        lines.push(format!("Missing answers for {}", paths.join(", ")));
+    }
+    if !problems.invalid.is_empty() {
+        if lines.is_empty() {
+            lines.push(format!("{} answer(s) break the rules of the template", problems.invalid.len()));
+        }
+        lines.extend(problems.invalid);
+    }
+    if !problems.references.is_empty() {
+        if lines.is_empty() {
+            lines.push(format!("{} reference(s) could not be resolved", problems.references.len()));
+        }
+        lines.extend(problems.references);
+    }
+    if !problems.assets.is_empty() {
+        if lines.is_empty() {
+            lines.push(format!("{} asset(s) could not be copied", problems.assets.len()));
+        }
+        lines.extend(problems.assets);
+    }
+    if !problems.templates.is_empty() {
+        if lines.is_empty() {
+            lines.push(format!("{} file template(s) could not be rendered", problems.templates.len()));
+        }
+        lines.extend(problems.templates);
+    }
+    if lines.is_empty() {
+        Ok(build_tree)
+    } else {
+        Err(Error::Validation(lines.join("\n")))
+    }
+}
+
+#[derive(Default)]
+struct Problems {
+    missing: Vec<NodeId>,
+    invalid: Vec<String>,
+    references: Vec<String>,
+    assets: Vec<String>,
+    templates: Vec<String>,
+}
+
+impl Problems {
+    // copies of a multi-select branch report the same problems, they are listed once
+    fn missing(&mut self, node_id: NodeId) {
+        if !self.missing.contains(&node_id) {
+            self.missing.push(node_id);
+        }
+    }
+
+    fn invalid(&mut self, message: String) {
+        if !self.invalid.contains(&message) {
+            self.invalid.push(message);
+        }
+    }
+
+    fn reference(&mut self, message: String) {
+        if !self.references.contains(&message) {
+            self.references.push(message);
+        }
+    }
+
+    fn asset(&mut self, message: String) {
+        if !self.assets.contains(&message) {
+            self.assets.push(message);
+        }
+    }
+
+    fn template(&mut self, message: String) {
+        if !self.templates.contains(&message) {
+            self.templates.push(message);
+        }
+    }
+
+    fn substitute_error(&mut self, tree: &Tree<LeafNodeType>, node_id: NodeId, error: SubstituteError) {
+        match error {
+            // the referenced question is reported instead of the node using it
+            SubstituteError::Unanswered(referenced) => self.missing(referenced),
+            e => self.reference(format!("{}: {}", tree.node_path(node_id), e.message(tree))),
+        }
+    }
+}
+
+/// The folder a `copy` node copies with everything in it.
+struct Copy<'a> {
+    tree: &'a Tree<LeafNodeType>,
+    node_id: NodeId,
+    scope: &'a Scope,
+    render_names: bool,
+}
+
+impl Copy<'_> {
+    /// Appends the files and folders in `dir` to `output_node`, sorted by name.
+    fn entries(&self, dir: &Path, output_node: &mut NodeMut<BuildNode>, problems: &mut Problems) {
+        let entries = fs::read_dir(dir)
+            .and_then(|entries| entries.map(|entry| entry.map(|entry| entry.path())).collect::<io::Result<Vec<_>>>());
+        let mut entries = match entries {
+            Ok(entries) => entries,
+            Err(e) => {
+                problems.asset(format!("{}: could not read `{}`: {}", self.tree.node_path(self.node_id), dir.display(), e));
+                return;
+            }
+        };
+        entries.sort();
+        for path in entries {
+            let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+            let name = match self.render_names {
+                true => substitute_in(self.tree, &name, self.scope).unwrap_or_else(|e| {
+                    problems.substitute_error(self.tree, self.node_id, e);
+                    name.clone()
+                }),
+                false => name,
+            };
+            if path.is_dir() {
+                let mut folder = output_node.append(BuildNode::folder(name));
+                self.entries(&path, &mut folder, problems);
+            } else {
+                output_node.append(BuildNode::file(name, FileContent::Path(path)));
+            }
+        }
+    }
+}
+
+fn build_node_of(tree: &Tree<LeafNodeType>, node: &NodeRef<LeafNodeType>, scope: &Scope, problems: &mut Problems) -> BuildNode {
+    let name = match resolved_answer_in(tree, node.node_id(), scope) {
+        Ok(Some(name)) => name,
+        Ok(None) => {
+            problems.missing(node.node_id());
+            String::new()
+        }
+        Err(e) => {
+            problems.substitute_error(tree, node.node_id(), e);
+            String::new()
+        }
+    };
+    if let LeafNodeType::TextInput { input, rules, transform: transforms, .. } = node.data() {
+        let input = transform(transforms, input);
+        // empty answers are already reported as missing
+        if !input.is_empty() {
+            if let Err(message) = rules.check(&input) {
+                problems.invalid(format!("{}: {}", tree.node_path(node.node_id()), message));
+            }
+        }
+    }
+    let build_node = match node.data() {
+        LeafNodeType::File { content: FileContent::Copy { from, .. }, .. } if from.is_dir() => BuildNode::folder(name),
+        LeafNodeType::File { content: FileContent::Copy { from, .. }, .. } => BuildNode::file(name, FileContent::Path(from.clone())),
+        LeafNodeType::File { content: FileContent::Template(content, origin), mode, .. } => {
+            BuildNode::file(name, rendered(tree, node, scope, content, origin.as_ref(), problems)).mode(*mode)
+        }
+        LeafNodeType::File { content: FileContent::Symlink(target), .. } => {
+            let target = target.to_string_lossy();
+            let target = substitute_in(tree, &target, scope).unwrap_or_else(|e| {
+                problems.substitute_error(tree, node.node_id(), e);
+                target.to_string()
+            });
+            BuildNode::file(name, FileContent::Symlink(target.into()))
+        }
+        LeafNodeType::File { name: _name, content, mode, .. } => BuildNode::file(name, content.clone()).mode(*mode),
+        _ => BuildNode::folder(name),
+    };
+    build_node.unique(is_unique(node))
+}
+
+/// Whether the node is built as a folder or file of this project alone, see [`BuildNode::unique`].
+///
+/// Folders named by a text input and everything a file node builds are, the folders of
+/// options and plain folders are shared with other projects.
+pub fn is_unique(node: &NodeRef<LeafNodeType>) -> bool {
+    match node.data() {
+        LeafNodeType::File { .. } => true,
+        // the root is the location the project is built in, it is expected to exist
+        LeafNodeType::TextInput { .. } => node.parent().is_some(),
+        _ => false,
+    }
+}
+
+/// The content of a file template filled in, problems point at the line in the content file,
+/// or in the template file for inline content. Inline content without an `origin` is named
+/// by its node.
+fn rendered(tree: &Tree<LeafNodeType>, node: &NodeRef<LeafNodeType>, scope: &Scope, content: &FileContent, origin: Option<&SourceLine>,
+            problems: &mut Problems) -> FileContent {
+    // the line of the content the first line of the text is on
+    let (file, first_line, source) = match content {
+        FileContent::Path(path) => match fs::read_to_string(path) {
+            Ok(source) => (path.display().to_string(), 1, source),
+            Err(e) => {
+                problems.template(format!("{}: could not read `{}`: {}", tree.node_path(node.node_id()), path.display(), e));
+                return FileContent::Empty;
+            }
+        },
+        FileContent::Inline(source) => match origin {
+            Some(origin) => (origin.file.clone(), origin.line, source.clone()),
+            None => (tree.node_path(node.node_id()), 1, source.clone()),
+        },
+        _ => return content.clone(),
+    };
+    match render(tree, scope, &source) {
+        Ok(text) => FileContent::Inline(text),
+        Err(e) => {
+            match e.line {
+                Some(line) => problems.template(format!("{}:{}: {}", file, first_line + line - 1, e.message)),
+                None => problems.template(format!("{}: {}", file, e.message)),
+            }
+            FileContent::Empty
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use crate::backend::answers::{apply_answer, collect_answers, Expect};
+    use crate::backend::yaml::{load_yaml, load_yaml_str};
+    use super::*;
+
+    fn answered(mut tree: Tree<LeafNodeType>, answers: &[(&str, &str)]) -> Tree<LeafNodeType> {
+        for (key, value) in answers {
+            apply_answer(&mut tree, key, value, Expect::Any).unwrap();
+        }
+        tree
+    }
+
+    fn names(tree: &Tree<LeafNodeType>) -> Vec<String> {
+        let plan = resolve_tree(tree).unwrap();
+        plan.root().unwrap().traverse_pre_order().map(|n| n.data().name.clone()).collect()
+    }
+
+    #[test]
+    fn test_references() {
+        let template = "\
+project:
+  default_location: /tmp/{{ProjectType}}
+  children:
+    - ProjectType:
+        options: [Song, Beat]
+    - ProjectName:
+        options: \"\"
+        children:
+          - \"{{ProjectName}}_stems\"
+          - \"{{ProjectName}}.als\":
+              file:
+";
+        let tree = load_yaml_str(template, "t.yaml").unwrap().0;
+        let err = resolve_tree(&tree).unwrap_err();
+        assert_eq!(err.to_string(), "Missing answers for ProjectType, ProjectName");
+
+        let plan = resolve_tree(&answered(tree, &[("ProjectType", "Beat"), ("ProjectName", "Tune")])).unwrap();
+        let names: Vec<String> = plan.root().unwrap().traverse_pre_order().map(|n| n.data().to_string()).collect();
+        assert_eq!(names, vec!["/tmp/Beat/", "Beat/", "Tune/", "Tune_stems/", "Tune.als"]);
+    }
+
+    #[test]
+    fn test_option_subtrees() {
+        let structure = |answers: &[(&str, &str)]| {
+            let tree = answered(load_yaml(Path::new("test/option_subtrees.yaml")).unwrap().0, &[("ProjectTest", "eins")]);
+            answered(tree, answers)
+        };
+
+        let names = names(&structure(&[("ProjectType", "Podcast"), ("ShowName", "Talk")]));
+        assert_eq!(names, vec!["/tmp/scaffolder-subtrees", "Podcast", "Talk", "Episodes", "Recordings", "Artwork", "eins", "test", "test2"]);
+
+        let err = resolve_tree(&structure(&[("ProjectType", "Song"), ("ShowName", "Talk")])).unwrap_err();
+        assert!(err.to_string().starts_with("Missing answers for ProjectType/ProjectName,"), "{}", err);
+    }
+
+    #[test]
+    fn test_multiselect() {
+        let tree = answered(load_yaml(Path::new("test/multiselect.yaml")).unwrap().0, &[
+            ("ProjectName", "Tune"),
+            ("DAW", "Ableton"),
+            ("DAW", "Fl Studio"),
+        ]);
+        assert_eq!(names(&tree), vec![
+            "/tmp/scaffolder-multiselect", "Tune",
+            "Ableton", "Tune_Ableton", "Samples",
+            "Fl Studio", "Tune_Fl Studio",
+            "Bounces",
+        ]);
+
+        let recorded = collect_answers(&tree);
+        let daws: Vec<&str> = recorded.entries().iter().filter(|(k, _)| k.ends_with("DAW")).map(|(_, v)| v.as_str()).collect();
+        assert_eq!(daws, vec!["Ableton", "Fl Studio"]);
+    }
+}
@@ -0,0 +1,168 @@
+use regex_lite::Regex;
+
+/// Checks the answer of a text input has to pass before the user can move on.
+///
+/// The answer is checked after its `transform`, the way it will be built.
+///
+/// ```yaml
+/// - ProjectName:
+///     options: ""
+///     rules:
+///       required: true
+///       min_length: 2
+///       charset: "a-zA-Z0-9 _-"
+///       path_component: true
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Rules {
+    /// An empty answer is not accepted, otherwise empty answers skip the other rules.
+    pub required: bool,
+    /// A regular expression the whole answer has to match.
+    pub pattern: Option<String>,
+    /// The length in characters.
+    pub min_length: Option<usize>,
+    pub max_length: Option<usize>,
+    /// The allowed characters, ranges like `a-z` included. A `-` at the start or end is literal.
+    pub charset: Option<String>,
+    /// The answer has to work as the name of a single folder or file.
+    pub path_component: bool,
+}
+
+impl Rules {
+    /// Explains the first rule `input` breaks, to be shown after the name of the node.
+    pub fn check(&self, input: &str) -> Result<(), String> {
+        if input.is_empty() {
+            return if self.required { Err("an answer is required".to_string()) } else { Ok(()) };
+        }
+        let length = input.chars().count();
+        if let Some(min) = self.min_length.filter(|min| length < *min) {
+            return Err(format!("needs at least {} characters", min));
+        }
+        if let Some(max) = self.max_length.filter(|max| length > *max) {
+            return Err(format!("can have at most {} characters", max));
+        }
+        if let Some(charset) = &self.charset {
+            if let Some(c) = input.chars().find(|c| !in_charset(charset, *c)) {
+                return Err(format!("`{}` is not allowed, only `{}` are", c, charset));
+            }
+        }
+        if self.path_component {
+            check_path_component(input)?;
+        }
+        if let Some(pattern) = &self.pattern {
+            // the validator reports broken patterns, trees built by hand are not checked
+            let regex = anchored(pattern).map_err(|e| format!("the pattern `{}` is invalid: {}", pattern, e))?;
+            if !regex.is_match(input) {
+                return Err(format!("has to match `{}`", pattern));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The pattern has to match the whole answer, not just a part of it.
+pub fn anchored(pattern: &str) -> Result<Regex, regex_lite::Error> {
+    Regex::new(&format!("^(?:{})$", pattern))
+}
+
+fn in_charset(charset: &str, c: char) -> bool {
+    let chars: Vec<char> = charset.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        if i + 2 < chars.len() && chars[i + 1] == '-' {
+            if (chars[i]..=chars[i + 2]).contains(&c) {
+                return true;
+            }
+            i += 3;
+        } else {
+            if chars[i] == c {
+                return true;
+            }
+            i += 1;
+        }
+    }
+    false
+}
+
+fn check_path_component(input: &str) -> Result<(), String> {
+    if input == "." || input == ".." {
+        return Err(format!("`{}` cannot be the name of a folder", input));
+    }
+    if let Some(c) = input.chars().find(|c| "/\\:*?\"<>|".contains(*c) || c.is_control()) {
+        return Err(match c.is_control() {
+            true => "control characters cannot be part of a folder name".to_string(),
+            false => format!("`{}` cannot be part of a folder name", c),
+        });
+    }
+    if input.trim() != input {
+        return Err("a folder name cannot start or end with a space".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use crate::backend::answers::{apply_answer, Expect};
+    use crate::backend::tree::resolve::resolve_tree;
+    use crate::backend::yaml::load_yaml;
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let rules = Rules { required: true, min_length: Some(2), max_length: Some(8), ..Rules::default() };
+        assert_eq!(rules.check(""), Err("an answer is required".to_string()));
+        assert_eq!(rules.check("a"), Err("needs at least 2 characters".to_string()));
+        assert_eq!(rules.check("Überlänge!"), Err("can have at most 8 characters".to_string()));
+        assert_eq!(rules.check("Tune"), Ok(()));
+
+        // optional answers may stay empty
+        let rules = Rules { min_length: Some(2), ..Rules::default() };
+        assert_eq!(rules.check(""), Ok(()));
+    }
+
+    #[test]
+    fn test_charset_and_pattern() {
+        let rules = Rules { charset: Some("a-zA-Z0-9 _-".to_string()), pattern: Some("[A-Z].*".to_string()), ..Rules::default() };
+        assert_eq!(rules.check("My Song-2_b"), Ok(()));
+        assert_eq!(rules.check("My/Song"), Err("`/` is not allowed, only `a-zA-Z0-9 _-` are".to_string()));
+        assert_eq!(rules.check("my song"), Err("has to match `[A-Z].*`".to_string()));
+        // the pattern has to match all of the answer
+        let rules = Rules { pattern: Some("[0-9]+".to_string()), ..Rules::default() };
+        assert!(rules.check("2024b").is_err());
+        // an invalid pattern lets nothing through
+        let rules = Rules { pattern: Some("[0-9".to_string()), ..Rules::default() };
+        assert!(rules.check("2024").is_err_and(|e| e.starts_with("the pattern `[0-9` is invalid")));
+    }
+
+    #[test]
+    fn test_path_component() {
+        let rules = Rules { path_component: true, ..Rules::default() };
+        assert_eq!(rules.check("Tune 2"), Ok(()));
+        assert_eq!(rules.check(".."), Err("`..` cannot be the name of a folder".to_string()));
+        assert_eq!(rules.check("a/b"), Err("`/` cannot be part of a folder name".to_string()));
+        assert!(rules.check("Tune ").is_err());
+        assert!(rules.check("a\tb").is_err());
+    }
+
+    #[test]
+    fn test_answers_that_break_the_rules() {
+        let rules = |answers: &[(&str, &str)]| {
+            let mut tree = load_yaml(Path::new("test/rules.yaml")).unwrap().0;
+            for (key, value) in answers {
+                apply_answer(&mut tree, key, value, Expect::Text).unwrap();
+            }
+            resolve_tree(&tree)
+        };
+
+        let err = rules(&[("ProjectName", "../Tune"), ("Year", "24")]).unwrap_err();
+        assert_eq!(err.to_string(), "\
+2 answer(s) break the rules of the template
+ProjectName: `/` cannot be part of a folder name
+Year: has to match `[0-9]{4}`");
+
+        let err = rules(&[]).unwrap_err();
+        assert_eq!(err.to_string(), "Missing answers for ProjectName, Year");
+        assert!(rules(&[("ProjectName", "Tune"), ("Year", "2024")]).is_ok());
+    }
+}
@@ -0,0 +1,56 @@
+use slab_tree::{NodeId, Tree};
+use crate::backend::tree::condition::NodeEnabled;
+use crate::backend::tree::nodes::LeafNodeType;
+
+/// Answers fixed for one copy of a multi-select branch.
+///
+/// Every checked item of a multi-select option gets its own copy of the children,
+/// inside a copy the option answers with that item only.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Scope(Vec<(NodeId, String)>);
+
+impl Scope {
+    pub fn with(&self, node_id: NodeId, answer: impl Into<String>) -> Scope {
+        let mut scope = self.clone();
+        scope.0.push((node_id, answer.into()));
+        scope
+    }
+
+    pub fn get(&self, node_id: NodeId) -> Option<&str> {
+        self.0.iter().rev().find(|(id, _)| *id == node_id).map(|(_, answer)| answer.as_str())
+    }
+}
+
+/// The answers of a node: fixed names, the input or the checked items.
+pub fn answers(tree: &Tree<LeafNodeType>, node_id: NodeId, scope: &Scope) -> Vec<String> {
+    if let Some(answer) = scope.get(node_id) {
+        return vec![answer.to_string()];
+    }
+    let answers = match tree.get(node_id).map(|node| node.data()) {
+        Some(LeafNodeType::Text { name, .. } | LeafNodeType::File { name, .. }) => vec![name.clone()],
+        Some(LeafNodeType::TextInput { name: _name, input, .. }) => vec![input.clone()],
+        Some(LeafNodeType::Option { name: _name, options, .. }) => options.selected(),
+        None => Vec::new(),
+    };
+    answers.into_iter().filter(|a| !a.is_empty()).collect()
+}
+
+/// The scopes of the copies of a node that get built.
+///
+/// None if the node is disabled, one per checked item of a multi-select option
+/// and a single one otherwise.
+pub fn copies(tree: &Tree<LeafNodeType>, node_id: NodeId, scope: &Scope) -> Vec<Scope> {
+    if !tree.is_enabled_in(node_id, scope) {
+        return Vec::new();
+    }
+    match tree.get(node_id).map(|node| node.data()) {
+        Some(LeafNodeType::Option { name: _name, options, .. }) if options.is_multiselect() && scope.get(node_id).is_none() => {
+            let selected = options.selected();
+            if selected.is_empty() {
+                return vec![scope.clone()];
+            }
+            selected.into_iter().map(|item| scope.with(node_id, item)).collect()
+        }
+        _ => vec![scope.clone()],
+    }
+}
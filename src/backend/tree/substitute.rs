@@ -0,0 +1,246 @@
+use std::ops::Range;
+use std::path::PathBuf;
+use slab_tree::{NodeId, Tree};
+use crate::backend::folder;
+use crate::backend::tree::nodes::{LeafNodeType, NodePath};
+use crate::backend::tree::scope::{answers, copies, Scope};
+use crate::backend::tree::tokens::{counter_of, next_counter, Token};
+use crate::backend::tree::transform::transform;
+
+/// A `{{Name}}` placeholder in a name, `range` covers the braces.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Placeholder {
+    pub key: String,
+    pub range: Range<usize>,
+}
+
+/// Why the placeholders of a name could not be replaced.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SubstituteError {
+    /// The referenced node has no answer yet.
+    Unanswered(NodeId),
+    /// No node of the tree has the referenced name.
+    Unknown(String),
+    /// The paths of the nodes that reference each other, the first one is repeated at the end.
+    Circular(Vec<String>),
+    /// A `{{` without its `}}`.
+    Unclosed(String),
+    /// A built-in token like `{{date}}` that could not be filled in.
+    Token(String),
+}
+
+impl SubstituteError {
+    pub fn message(&self, tree: &Tree<LeafNodeType>) -> String {
+        match self {
+            SubstituteError::Unanswered(id) => format!("`{}` has no answer yet", tree.node_path(*id)),
+            SubstituteError::Unknown(key) => format!("unknown reference `{{{{{}}}}}`", key),
+            SubstituteError::Circular(chain) => format!("circular reference {}", chain.join(" -> ")),
+            SubstituteError::Unclosed(text) => format!("`{}` has a `{{{{` without `}}}}`", text),
+            SubstituteError::Token(message) => message.clone(),
+        }
+    }
+}
+
+/// Finds the placeholders in `text`.
+pub fn placeholders(text: &str) -> Result<Vec<Placeholder>, SubstituteError> {
+    let mut found = Vec::new();
+    let mut offset = 0;
+    while let Some(start) = text[offset..].find("{{").map(|i| offset + i) {
+        let Some(len) = text[start + 2..].find("}}") else {
+            return Err(SubstituteError::Unclosed(text.to_string()));
+        };
+        let end = start + 2 + len + 2;
+        found.push(Placeholder { key: text[start + 2..end - 2].trim().to_string(), range: start..end });
+        offset = end;
+    }
+    Ok(found)
+}
+
+/// Replaces the placeholders in `text` with the answers of the nodes they name.
+///
+/// A placeholder names a node the same way answers do, by its name or the end of its path.
+/// The checked items of a multi-select option are joined with `, `. Placeholders that name
+/// no node can be built-in tokens like `{{date}}`, see [`Token`].
+pub fn substitute(tree: &Tree<LeafNodeType>, text: &str) -> Result<String, SubstituteError> {
+    substitute_with(tree, text, &Scope::default(), &mut Vec::new())
+}
+
+/// Like [`substitute`] inside one copy of a multi-select branch.
+pub fn substitute_in(tree: &Tree<LeafNodeType>, text: &str, scope: &Scope) -> Result<String, SubstituteError> {
+    substitute_with(tree, text, scope, &mut Vec::new())
+}
+
+/// The answer of a node with its placeholders replaced and the `transform` of a text input
+/// applied, `None` if it has no answer.
+pub fn resolved_answer(tree: &Tree<LeafNodeType>, node_id: NodeId) -> Result<Option<String>, SubstituteError> {
+    resolved_answer_in(tree, node_id, &Scope::default())
+}
+
+/// Like [`resolved_answer`] inside one copy of a multi-select branch.
+pub fn resolved_answer_in(tree: &Tree<LeafNodeType>, node_id: NodeId, scope: &Scope) -> Result<Option<String>, SubstituteError> {
+    resolve_node(tree, node_id, scope, &mut Vec::new())
+}
+
+fn resolve_node(tree: &Tree<LeafNodeType>, node_id: NodeId, scope: &Scope, stack: &mut Vec<NodeId>) -> Result<Option<String>, SubstituteError> {
+    if let Some(pos) = stack.iter().position(|id| *id == node_id) {
+        let mut chain: Vec<String> = stack[pos..].iter().map(|id| tree.node_path(*id)).collect();
+        chain.push(tree.node_path(node_id));
+        return Err(SubstituteError::Circular(chain));
+    }
+    let raw = answers(tree, node_id, scope).join(", ");
+    if raw.is_empty() {
+        return Ok(None);
+    }
+
+    stack.push(node_id);
+    let answer = substitute_with(tree, &raw, scope, stack);
+    stack.pop();
+    let answer = match tree.get(node_id).map(|node| node.data()) {
+        Some(LeafNodeType::TextInput { transform: transforms, .. }) => transform(transforms, &answer?),
+        _ => answer?,
+    };
+    // a text that is only spaces can be trimmed away
+    Ok(Some(answer).filter(|answer| !answer.is_empty()))
+}
+
+fn substitute_with(tree: &Tree<LeafNodeType>, text: &str, scope: &Scope, stack: &mut Vec<NodeId>) -> Result<String, SubstituteError> {
+    let mut substituted = String::new();
+    let mut last = 0;
+    let mut counter_width = 1;
+    for placeholder in placeholders(text)? {
+        substituted.push_str(&text[last..placeholder.range.start]);
+        last = placeholder.range.end;
+        let Some(node_id) = tree.find_node(&placeholder.key) else {
+            match Token::parse(&placeholder.key) {
+                None => return Err(SubstituteError::Unknown(placeholder.key)),
+                Some(Err(message)) => return Err(SubstituteError::Token(message)),
+                Some(Ok(Token::Counter(_))) if substituted.contains(COUNTER) => {
+                    return Err(SubstituteError::Token(String::from("`{{counter}}` can only be used once in a name")));
+                }
+                Some(Ok(Token::Counter(width))) if !stack.is_empty() => {
+                    substituted.push(COUNTER);
+                    counter_width = width;
+                }
+                Some(Ok(token)) => substituted.push_str(&token.value().map_err(SubstituteError::Token)?),
+            }
+            continue;
+        };
+        match resolve_node(tree, node_id, scope, stack)? {
+            Some(answer) => substituted.push_str(&answer),
+            None => return Err(SubstituteError::Unanswered(node_id)),
+        }
+    }
+    substituted.push_str(&text[last..]);
+    match stack.last() {
+        Some(node_id) if substituted.contains(COUNTER) => numbered(tree, *node_id, scope, stack, &substituted, counter_width),
+        _ => Ok(substituted),
+    }
+}
+
+/// Stands in for a `{{counter}}` until the rest of the name is known.
+const COUNTER: char = '\u{0}';
+
+/// Replaces the counter in the name of a node with the next free number in the folder the
+/// node is built in, after the folders on disk and the ones built before it by this run.
+fn numbered(tree: &Tree<LeafNodeType>, node_id: NodeId, scope: &Scope, stack: &mut Vec<NodeId>, name: &str, width: usize) -> Result<String, SubstituteError> {
+    let dir = parent_dir(tree, node_id, scope, stack)?;
+    let (prefix, suffix) = name.split_once(COUNTER).unwrap_or((name, ""));
+    let planned = planned_before(tree, node_id, scope, stack, prefix, suffix);
+    let number = next_counter(&dir, prefix, suffix).max(planned.map_or(1, |n| n + 1));
+    Ok(name.replace(COUNTER, &format!("{:0width$}", number, width = width)))
+}
+
+/// The highest number among the siblings built before the node, with the same name around it.
+fn planned_before(tree: &Tree<LeafNodeType>, node_id: NodeId, scope: &Scope, stack: &mut Vec<NodeId>, prefix: &str, suffix: &str) -> Option<u64> {
+    let node = tree.get(node_id)?;
+    let siblings: Vec<NodeId> = node.parent()?.children().map(|child| child.node_id()).take_while(|id| *id != node_id).collect();
+    let mut highest = None;
+    for sibling in siblings {
+        for sibling_scope in copies(tree, sibling, scope) {
+            // a sibling that cannot be named reports the problem itself
+            let Ok(Some(name)) = resolve_node(tree, sibling, &sibling_scope, stack) else { continue };
+            highest = highest.max(counter_of(&name, prefix, suffix));
+        }
+    }
+    highest
+}
+
+/// The folder on disk a node is built in.
+fn parent_dir(tree: &Tree<LeafNodeType>, node_id: NodeId, scope: &Scope, stack: &mut Vec<NodeId>) -> Result<PathBuf, SubstituteError> {
+    let ancestors: Vec<NodeId> = tree.get(node_id).into_iter().flat_map(|node| node.ancestors()).map(|a| a.node_id()).collect();
+    let Some((root, folders)) = ancestors.split_last() else {
+        return Err(SubstituteError::Token(String::from("`{{counter}}` only works in the names of folders and files")));
+    };
+    let location = resolve_node(tree, *root, scope, stack)?.ok_or(SubstituteError::Unanswered(*root))?;
+    let mut dir = folder::resolve_location(&location).map_err(|e| SubstituteError::Token(e.to_string()))?;
+    for folder_id in folders.iter().rev() {
+        dir.push(resolve_node(tree, *folder_id, scope, stack)?.ok_or(SubstituteError::Unanswered(*folder_id))?);
+    }
+    Ok(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use slab_tree::TreeBuilder;
+    use crate::backend::tree::rules::Rules;
+    use super::*;
+
+    fn input(name: &str, input: &str) -> LeafNodeType {
+        LeafNodeType::TextInput { name: name.to_string(), input: input.to_string(), rules: Rules::default(), transform: Vec::new(), when: None }
+    }
+
+    #[test]
+    fn test_placeholders() {
+        let found = placeholders("{{ ProjectType }}/{{ProjectName}}.als").unwrap();
+        let keys: Vec<&str> = found.iter().map(|p| p.key.as_str()).collect();
+        assert_eq!(keys, vec!["ProjectType", "ProjectName"]);
+        assert_eq!(found[1].range, 18..33);
+        assert!(placeholders("{{ProjectName").is_err());
+    }
+
+    #[test]
+    fn test_substitute() {
+        let mut tree = TreeBuilder::new().with_root(input("Location", "~/music")).build();
+        let mut root = tree.root_mut().unwrap();
+        let mut name = root.append(input("ProjectName", "Tune"));
+        let stems = name.append(LeafNodeType::Text { name: String::from("{{ProjectName}}_stems"), when: None }).node_id();
+        let empty = root.append(input("Artist", "")).node_id();
+
+        assert_eq!(resolved_answer(&tree, stems).unwrap().as_deref(), Some("Tune_stems"));
+        assert_eq!(substitute(&tree, "{{Location}}/{{ProjectName}}.als").unwrap(), "~/music/Tune.als");
+        assert_eq!(substitute(&tree, "{{Artist}}"), Err(SubstituteError::Unanswered(empty)));
+        assert_eq!(substitute(&tree, "{{Nope}}"), Err(SubstituteError::Unknown(String::from("Nope"))));
+    }
+
+    #[test]
+    fn test_circular_reference() {
+        let mut tree = TreeBuilder::new().with_root(input("Location", "~/music")).build();
+        let mut root = tree.root_mut().unwrap();
+        root.append(input("A", "{{B}}"));
+        root.append(input("B", "x{{A}}"));
+
+        let err = substitute(&tree, "{{A}}").unwrap_err();
+        assert_eq!(err, SubstituteError::Circular(vec![String::from("A"), String::from("B"), String::from("A")]));
+        assert_eq!(err.message(&tree), "circular reference A -> B -> A");
+    }
+
+    #[test]
+    fn test_counters() {
+        let location = std::env::temp_dir().join("scaffolder_counters");
+        let _ = std::fs::remove_dir_all(&location);
+        std::fs::create_dir_all(location.join("Take_02")).unwrap();
+        let mut tree = TreeBuilder::new().with_root(input("Location", &location.to_string_lossy())).build();
+        let mut root = tree.root_mut().unwrap();
+        let text = |name: &str| LeafNodeType::Text { name: name.to_string(), when: None };
+        let first = root.append(text("Take_{{counter:2}}")).node_id();
+        root.append(text("Mix_{{counter}}"));
+        let second = root.append(text("Take_{{counter:2}}")).node_id();
+        let twice = root.append(text("Take_{{counter}}_{{counter}}")).node_id();
+
+        // numbered after the take on disk and the one planned before it
+        assert_eq!(resolved_answer(&tree, first).unwrap().as_deref(), Some("Take_03"));
+        assert_eq!(resolved_answer(&tree, second).unwrap().as_deref(), Some("Take_04"));
+        assert!(matches!(resolved_answer(&tree, twice), Err(SubstituteError::Token(_))));
+        std::fs::remove_dir_all(location).unwrap();
+    }
+}
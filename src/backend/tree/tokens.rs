@@ -0,0 +1,141 @@
+use std::fs;
+use std::path::Path;
+use chrono::format::{Item, StrftimeItems};
+use chrono::Local;
+
+/// A built-in placeholder that does not name a node, e.g. `{{date:%Y-%m-%d}}`.
+///
+/// A node with the same name as a bare token (`{{user}}`) is used instead of the token.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    /// The current date in a strftime format, `%Y-%m-%d` by default.
+    Date(String),
+    /// The current time in a strftime format, `%H-%M-%S` by default.
+    Time(String),
+    User,
+    Hostname,
+    /// The next number among the sibling folders on disk and the ones planned before it, zero
+    /// padded to the width. A name can have one counter.
+    Counter(usize),
+}
+
+pub const DATE_FORMAT: &str = "%Y-%m-%d";
+pub const TIME_FORMAT: &str = "%H-%M-%S";
+
+impl Token {
+    /// The token written as `key`, `None` if it is no built-in token and an error if its
+    /// argument is broken.
+    pub fn parse(key: &str) -> Option<Result<Token, String>> {
+        let (name, argument) = match key.split_once(':') {
+            Some((name, argument)) => (name.trim(), Some(argument)),
+            None => (key, None),
+        };
+        let token = match (name, argument) {
+            ("date", format) => date_format(format.unwrap_or(DATE_FORMAT)).map(Token::Date),
+            ("time", format) => date_format(format.unwrap_or(TIME_FORMAT)).map(Token::Time),
+            ("user", None) => Ok(Token::User),
+            ("hostname", None) => Ok(Token::Hostname),
+            ("counter", None) => Ok(Token::Counter(1)),
+            ("counter", Some(width)) => match width.trim().parse() {
+                Ok(width) if width > 0 => Ok(Token::Counter(width)),
+                _ => Err(format!("the width of `{{{{{}}}}}` has to be a number above 0", key)),
+            },
+            ("user" | "hostname", Some(_)) => Err(format!("`{{{{{}}}}}` takes no argument", name)),
+            _ => return None,
+        };
+        Some(token)
+    }
+
+    /// The value of the token, counters are numbered by [`next_counter`] where the folder they
+    /// are built in is known.
+    pub fn value(&self) -> Result<String, String> {
+        match self {
+            Token::Date(format) | Token::Time(format) => Ok(Local::now().format(format).to_string()),
+            Token::User => ["USER", "USERNAME", "LOGNAME"].iter()
+                .find_map(|var| std::env::var(var).ok().filter(|user| !user.is_empty()))
+                .ok_or_else(|| String::from("`{{user}}` needs USER to be set")),
+            Token::Hostname => Ok(gethostname::gethostname().to_string_lossy().to_string()),
+            Token::Counter(_) => Err(String::from("`{{counter}}` only works in the names of folders and files")),
+        }
+    }
+}
+
+fn date_format(format: &str) -> Result<String, String> {
+    // chrono panics when an invalid format is printed
+    if StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) {
+        return Err(format!("`{}` is not a valid date format", format));
+    }
+    Ok(format.to_string())
+}
+
+/// One above the highest number of the entries of `dir` named `prefix`, a number and `suffix`,
+/// or 1 if there are none.
+pub fn next_counter(dir: &Path, prefix: &str, suffix: &str) -> u64 {
+    let Ok(entries) = fs::read_dir(dir) else { return 1 };
+    let highest = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| counter_of(&entry.file_name().to_string_lossy(), prefix, suffix))
+        .max();
+    highest.map_or(1, |n| n + 1)
+}
+
+/// The number of a name made of `prefix`, a number and `suffix`.
+pub fn counter_of(name: &str, prefix: &str, suffix: &str) -> Option<u64> {
+    let number = name.strip_prefix(prefix)?.strip_suffix(suffix)?;
+    number.chars().all(|c| c.is_ascii_digit()).then(|| number.parse().ok()).flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backend::answers::{apply_answer, Expect};
+    use crate::backend::tree::resolve::resolve_tree;
+    use crate::backend::yaml::load_yaml;
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Token::parse("date"), Some(Ok(Token::Date(DATE_FORMAT.to_string()))));
+        assert_eq!(Token::parse("date:%d.%m.%Y"), Some(Ok(Token::Date("%d.%m.%Y".to_string()))));
+        assert_eq!(Token::parse("counter:3"), Some(Ok(Token::Counter(3))));
+        assert_eq!(Token::parse("ProjectName"), None);
+        assert!(matches!(Token::parse("date:%Q"), Some(Err(_))));
+        assert!(matches!(Token::parse("counter:x"), Some(Err(_))));
+        assert!(matches!(Token::parse("user:me"), Some(Err(_))));
+    }
+
+    #[test]
+    fn test_next_counter() {
+        let dir = std::env::temp_dir().join("scaffolder_next_counter");
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(next_counter(&dir, "Take_", ""), 1);
+        for name in ["Take_001", "Take_007", "Take_x", "Take_012.wav", "Mix_020"] {
+            fs::create_dir_all(dir.join(name)).unwrap();
+        }
+        assert_eq!(next_counter(&dir, "Take_", ""), 8);
+        assert_eq!(next_counter(&dir, "Take_", ".wav"), 13);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_tokens_in_names() {
+        let location = std::env::temp_dir().join("scaffolder_tokens");
+        let _ = fs::remove_dir_all(&location);
+        let names = || -> Vec<String> {
+            let mut tree = load_yaml(Path::new("test/tokens.yaml")).unwrap().0;
+            apply_answer(&mut tree, "Location", &location.to_string_lossy(), Expect::Text).unwrap();
+            apply_answer(&mut tree, "SessionName", "Live", Expect::Text).unwrap();
+            let plan = resolve_tree(&tree).unwrap();
+            let names = plan.root().unwrap().traverse_pre_order().skip(1).map(|n| n.data().name.clone()).collect();
+            names
+        };
+        let session = format!("{}_Live", Local::now().format("%Y-%m-%d"));
+        let hostname = gethostname::gethostname().to_string_lossy().to_string();
+
+        assert_eq!(names(), vec!["Live".to_string(), "Archive".to_string(), session.clone(), "Take_001".to_string(), hostname]);
+
+        // the counter continues after the takes already on disk
+        fs::create_dir_all(location.join("Archive").join(&session).join("Take_004")).unwrap();
+        assert!(names().contains(&"Take_005".to_string()));
+        fs::remove_dir_all(location).unwrap();
+    }
+}
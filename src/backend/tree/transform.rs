@@ -0,0 +1,144 @@
+use serde::Deserialize;
+
+/// Rewrites the answer of a text input before it becomes a name, e.g. `transform: slug`.
+///
+/// The input keeps the text as typed, only the resolved name is transformed.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Transform {
+    /// `My New Song ` becomes `my-new-song`.
+    Slug,
+    /// `My New Song` becomes `my_new_song`.
+    SnakeCase,
+    /// `My New Song` becomes `my-new-song`, like `slug` but keeping characters like `.`.
+    #[serde(rename = "kebab-case")]
+    KebabCase,
+    Lowercase,
+    Uppercase,
+    /// `my new SONG` becomes `My New Song`.
+    Title,
+    /// Removes spaces at the start and the end.
+    Trim,
+    /// Replaces every space with `_`.
+    #[serde(rename = "replace-spaces")]
+    ReplaceSpaces,
+}
+
+/// The names of the transforms in templates, in the order of [`Transform::ALL`].
+pub const TRANSFORM_NAMES: &[&str] = &["slug", "snake_case", "kebab-case", "lowercase", "uppercase", "title", "trim", "replace-spaces"];
+
+impl Transform {
+    pub const ALL: [Transform; 8] = [
+        Transform::Slug, Transform::SnakeCase, Transform::KebabCase, Transform::Lowercase,
+        Transform::Uppercase, Transform::Title, Transform::Trim, Transform::ReplaceSpaces,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Transform> {
+        TRANSFORM_NAMES.iter().position(|n| *n == name).map(|i| Transform::ALL[i])
+    }
+
+    pub fn apply(&self, text: &str) -> String {
+        match self {
+            Transform::Slug => words(text, char::is_alphanumeric).join("-"),
+            Transform::SnakeCase => words(text, |c| !c.is_whitespace() && c != '-' && c != '_').join("_"),
+            Transform::KebabCase => words(text, |c| !c.is_whitespace() && c != '-' && c != '_').join("-"),
+            Transform::Lowercase => text.to_lowercase(),
+            Transform::Uppercase => text.to_uppercase(),
+            Transform::Title => title(text),
+            Transform::Trim => text.trim().to_string(),
+            Transform::ReplaceSpaces => text.replace(' ', "_"),
+        }
+    }
+}
+
+/// Applies the transforms of a node one after the other.
+pub fn transform(transforms: &[Transform], text: &str) -> String {
+    transforms.iter().fold(text.to_string(), |text, t| t.apply(&text))
+}
+
+/// The lowercase words of `text`, split at characters that are not part of a word
+/// and where a lowercase letter is followed by an uppercase one (`MySong`).
+fn words(text: &str, in_word: impl Fn(char) -> bool) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut previous_lower = false;
+    for c in text.chars() {
+        let boundary = !in_word(c) || (previous_lower && c.is_uppercase());
+        if boundary && !word.is_empty() {
+            words.push(std::mem::take(&mut word));
+        }
+        if in_word(c) {
+            word.extend(c.to_lowercase());
+        }
+        previous_lower = c.is_lowercase();
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+fn title(text: &str) -> String {
+    let mut titled = String::new();
+    let mut word_start = true;
+    for c in text.chars() {
+        if word_start {
+            titled.extend(c.to_uppercase());
+        } else {
+            titled.extend(c.to_lowercase());
+        }
+        word_start = c.is_whitespace();
+    }
+    titled
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use crate::backend::answers::{apply_answer, Expect};
+    use crate::backend::tree::resolve::resolve_tree;
+    use crate::backend::yaml::load_yaml;
+    use super::*;
+
+    #[test]
+    fn test_transforms() {
+        let applied: Vec<String> = Transform::ALL.iter().map(|t| t.apply(" My NewSong v1.2 ")).collect();
+        assert_eq!(applied, vec![
+            "my-new-song-v1-2",
+            "my_new_song_v1.2",
+            "my-new-song-v1.2",
+            " my newsong v1.2 ",
+            " MY NEWSONG V1.2 ",
+            " My Newsong V1.2 ",
+            "My NewSong v1.2",
+            "_My_NewSong_v1.2_",
+        ]);
+        assert_eq!(Transform::from_name("kebab-case"), Some(Transform::KebabCase));
+        assert_eq!(Transform::from_name("camelCase"), None);
+    }
+
+    #[test]
+    fn test_transform_order() {
+        assert_eq!(transform(&[Transform::Trim, Transform::ReplaceSpaces], " My Song "), "My_Song");
+        assert_eq!(transform(&[Transform::ReplaceSpaces, Transform::Trim], " My Song "), "_My_Song_");
+        assert_eq!(transform(&[], " My Song "), " My Song ");
+    }
+
+    #[test]
+    fn test_transformed_answers() {
+        let transformed = |name: &str, set_name: &str| {
+            let mut tree = load_yaml(Path::new("test/transform.yaml")).unwrap().0;
+            apply_answer(&mut tree, "ProjectName", name, Expect::Text).unwrap();
+            apply_answer(&mut tree, "SetName", set_name, Expect::Text).unwrap();
+            resolve_tree(&tree)
+        };
+
+        let plan = transformed("My New Song ", " Live Set ").unwrap();
+        let names: Vec<String> = plan.root().unwrap().traverse_pre_order().map(|n| n.data().name.clone()).collect();
+        assert_eq!(names, vec!["/tmp/scaffolder-transform", "my-new-song", "my-new-song_stems", "Live_Set"]);
+
+        // the rules check the transformed name, a name of spaces only is no answer
+        let err = transformed("A !", "  ").unwrap_err();
+        assert_eq!(err.to_string(), "Missing answers for SetName\nProjectName: needs at least 3 characters");
+    }
+}
@@ -0,0 +1,237 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde_yaml::Value;
+use crate::backend::yaml::diagnostic::{Diagnostic, Diagnostics};
+use crate::backend::yaml::reader::read_file;
+use crate::backend::yaml::source_map::{Segment, SourceMap};
+use crate::backend::yaml::validator::serde_diagnostic;
+use crate::error::{Error, Result};
+
+/// A template that can be chosen by name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TemplateEntry {
+    pub name: String,
+    pub description: Option<String>,
+    /// The file the template is in.
+    pub file: PathBuf,
+    /// The key of the template below `templates:`, `None` if the whole file is the template.
+    pub key: Option<String>,
+    /// Why the file could not be read, it only fails the run when it is chosen.
+    pub problem: Option<String>,
+}
+
+/// The environment variable that points at a template file or a directory of them.
+pub const TEMPLATE_ENV: &str = "SCAFFOLDER_TEMPLATE";
+
+/// Where templates are looked for when no `--template` is given.
+///
+/// In order of precedence, a template found earlier hides one with the same name found later:
+/// 1. the file or directory in `SCAFFOLDER_TEMPLATE`
+/// 2. `template.yaml` in the current directory
+/// 3. the closest `.scaffolder/` directory in the current directory or one of its parents
+/// 4. `$XDG_CONFIG_HOME/scaffolder/templates`, `~/.config/scaffolder/templates` without it
+#[derive(Debug, Clone)]
+pub struct TemplateSearch {
+    pub env: Option<PathBuf>,
+    pub current_dir: PathBuf,
+    pub config_home: Option<PathBuf>,
+}
+
+impl TemplateSearch {
+    /// The search of the current process.
+    pub fn from_env() -> TemplateSearch {
+        let config_home = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            // relative paths are invalid and to be ignored, says the spec
+            .filter(|p| p.is_absolute())
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")));
+        TemplateSearch {
+            env: std::env::var_os(TEMPLATE_ENV).filter(|v| !v.is_empty()).map(PathBuf::from),
+            current_dir: std::env::current_dir().unwrap_or_default(),
+            config_home,
+        }
+    }
+
+    /// The places to look in, highest precedence first, whether they exist or not.
+    pub fn locations(&self) -> Vec<PathBuf> {
+        let mut locations: Vec<PathBuf> = self.env.iter().cloned().collect();
+        locations.push(self.current_dir.join("template.yaml"));
+        let project = self.current_dir.ancestors().map(|dir| dir.join(".scaffolder")).find(|dir| dir.is_dir());
+        locations.extend(project);
+        locations.extend(self.config_home.as_ref().map(|config| config.join("scaffolder").join("templates")));
+        locations
+    }
+}
+
+/// The templates of every location of `search`, highest precedence first.
+///
+/// The location in `SCAFFOLDER_TEMPLATE` has to exist, the others are skipped when they don't.
+/// A file that cannot be read is listed with its [`problem`](TemplateEntry::problem).
+pub fn discover_templates(search: &TemplateSearch) -> Result<Vec<TemplateEntry>> {
+    let mut templates = Vec::new();
+    for location in search.locations() {
+        if location.exists() || search.env.as_ref() == Some(&location) {
+            templates.extend(list_templates(&location)?);
+        }
+    }
+    Ok(templates)
+}
+
+/// The templates without the ones hidden by an earlier template with the same name.
+pub fn visible_templates(templates: &[TemplateEntry]) -> Vec<TemplateEntry> {
+    let mut visible: Vec<TemplateEntry> = Vec::new();
+    for template in templates {
+        if find_template(&visible, &template.name).is_none() {
+            visible.push(template.clone());
+        }
+    }
+    visible
+}
+
+/// The templates in a file, or in the `.yaml` files of a directory.
+///
+/// A file is either a single template named after the file or holds several below `templates:`.
+/// Files without a `project`, like the fragments templates include, are left out, and a file
+/// that cannot be read is listed under its name with the problem.
+///
+/// ```yaml
+/// templates:
+///   song:
+///     description: A song with stems and bounces
+///     project:
+///       default_location: "~/music"
+///   podcast:
+///     description: An episode with its recordings
+///     project:
+///       default_location: "~/podcast"
+/// ```
+pub fn list_templates(path: &Path) -> Result<Vec<TemplateEntry>> {
+    if !path.is_dir() {
+        // a path that is not there at all is a wrong argument, not a broken template
+        fs::metadata(path).map_err(|source| Error::TemplateRead { path: path.to_path_buf(), source })?;
+        return Ok(entries_in(path));
+    }
+    let read_error = |source| Error::TemplateRead { path: path.to_path_buf(), source };
+    let mut files = Vec::new();
+    for dir_entry in fs::read_dir(path).map_err(read_error)? {
+        let file = dir_entry.map_err(read_error)?.path();
+        if file.is_file() && matches!(file.extension().and_then(|e| e.to_str()), Some("yaml" | "yml")) {
+            files.push(file);
+        }
+    }
+    files.sort();
+
+    let mut entries = Vec::new();
+    for file in files {
+        entries.extend(entries_in(&file));
+    }
+    Ok(entries)
+}
+
+/// The templates that can be picked, without the ones whose file is broken.
+pub fn usable_templates(templates: &[TemplateEntry]) -> Vec<TemplateEntry> {
+    templates.iter().filter(|t| t.problem.is_none()).cloned().collect()
+}
+
+/// The template called `name` among `templates`.
+pub fn find_template<'a>(templates: &'a [TemplateEntry], name: &str) -> Option<&'a TemplateEntry> {
+    templates.iter().find(|t| t.name == name)
+}
+
+fn entries_in(file: &Path) -> Vec<TemplateEntry> {
+    templates_in(file).unwrap_or_else(|e| {
+        let name = file.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+        // the first problem is enough to tell, loading the template shows all of them
+        let problem = match e {
+            Error::TemplateParse(diagnostics) => diagnostics.0.first().map(|d| d.message.clone()).unwrap_or_default(),
+            other => other.to_string(),
+        };
+        vec![TemplateEntry { name, description: None, file: file.to_path_buf(), key: None, problem: Some(problem) }]
+    })
+}
+
+fn templates_in(file: &Path) -> Result<Vec<TemplateEntry>> {
+    let source = read_file(file)?;
+    let file_name = file.to_string_lossy();
+    let source_map = SourceMap::new(&source);
+    let value: Value = serde_yaml::from_str(&source)
+        .map_err(|e| Diagnostics::from(serde_diagnostic(&e, &file_name, &source_map)))?;
+    let description = |template: &Value| template.get("description").and_then(Value::as_str).map(str::to_string);
+    // a variant can take its whole project from the template it extends
+    let is_template = |template: &Value| template.get("project").is_some() || template.get("extends").is_some();
+
+    let Some(templates) = value.get("templates") else {
+        if !is_template(&value) {
+            return Ok(Vec::new());
+        }
+        let name = file.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+        return Ok(vec![TemplateEntry { name, description: description(&value), file: file.to_path_buf(), key: None, problem: None }]);
+    };
+    let Some(templates) = templates.as_mapping() else {
+        let span = source_map.span(&[Segment::Key(String::from("templates"))]);
+        let diagnostic = Diagnostic::new(&file_name, "`templates` has to map names to templates", span, &source_map)
+            .with_help("write every template below `templates:` as its name followed by its `project`");
+        return Err(Diagnostics::from(diagnostic).into());
+    };
+    Ok(templates.iter()
+        .filter(|(_, template)| is_template(template))
+        .filter_map(|(key, template)| {
+            let key = key.as_str()?;
+            Some(TemplateEntry { name: key.to_string(), description: description(template), file: file.to_path_buf(), key: Some(key.to_string()), problem: None })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_templates() {
+        let templates = list_templates(Path::new("test/catalog")).unwrap();
+        let names: Vec<&str> = templates.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["song", "podcast", "video"]);
+        assert_eq!(templates[1].description.as_deref(), Some("An episode with its recordings"));
+        assert_eq!(templates[1].key.as_deref(), Some("podcast"));
+        assert_eq!(templates[2].key, None);
+        assert_eq!(find_template(&templates, "video").map(|t| t.file.clone()), Some(PathBuf::from("test/catalog/video.yaml")));
+    }
+
+    #[test]
+    fn test_discover_templates() {
+        let search = TemplateSearch {
+            env: None,
+            current_dir: PathBuf::from("test/discovery/project/session"),
+            config_home: Some(PathBuf::from("test/discovery/config")),
+        };
+        assert_eq!(search.locations(), vec![
+            PathBuf::from("test/discovery/project/session/template.yaml"),
+            PathBuf::from("test/discovery/project/.scaffolder"),
+            PathBuf::from("test/discovery/config/scaffolder/templates"),
+        ]);
+
+        // the fragment is left out and the broken file does not stop the others from being found
+        let templates = discover_templates(&search).unwrap();
+        let files: Vec<&Path> = templates.iter().map(|t| t.file.as_path()).collect();
+        assert_eq!(files, vec![
+            Path::new("test/discovery/project/.scaffolder/song.yaml"),
+            Path::new("test/discovery/config/scaffolder/templates/broken.yaml"),
+            Path::new("test/discovery/config/scaffolder/templates/song.yaml"),
+            Path::new("test/discovery/config/scaffolder/templates/video.yaml"),
+        ]);
+        assert!(templates[1].problem.as_deref().is_some_and(|p| p.starts_with("did not find expected key")));
+        assert!(templates.iter().filter(|t| t.name != "broken").all(|t| t.problem.is_none()));
+        let visible = visible_templates(&templates);
+        let names: Vec<&str> = visible.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["song", "broken", "video"]);
+        assert_eq!(visible[0].file, templates[0].file);
+        let usable = usable_templates(&visible);
+        let names: Vec<&str> = usable.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["song", "video"]);
+
+        let env = TemplateSearch { env: Some(PathBuf::from("test/catalog/video.yaml")), ..search.clone() };
+        assert_eq!(discover_templates(&env).unwrap()[0].name, "video");
+        let missing = TemplateSearch { env: Some(PathBuf::from("test/nope.yaml")), ..search };
+        assert!(discover_templates(&missing).is_err());
+    }
+}
@@ -0,0 +1,90 @@
+use std::fmt::{Display, Formatter};
+use crate::backend::yaml::source_map::{SourceMap, Span};
+
+/// A problem in a template, pointing at the line and column it was found at.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub file: String,
+    pub message: String,
+    pub span: Option<Span>,
+    pub snippet: Option<String>,
+    pub help: Option<String>,
+}
+
+/// All problems found in a template, they are reported together.
+#[derive(Debug, Clone, Default)]
+pub struct Diagnostics(pub Vec<Diagnostic>);
+
+impl Diagnostic {
+    pub fn new(file: &str, message: impl Into<String>, span: Option<Span>, source_map: &SourceMap) -> Diagnostic {
+        Diagnostic {
+            file: file.to_string(),
+            message: message.into(),
+            span,
+            snippet: span.and_then(|s| source_map.line(s.line)).map(|l| l.to_string()),
+            help: None,
+        }
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Diagnostic {
+        self.help = Some(help.into());
+        self
+    }
+}
+
+impl Diagnostics {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn push(&mut self, diagnostic: Diagnostic) {
+        self.0.push(diagnostic);
+    }
+}
+
+impl From<Diagnostic> for Diagnostics {
+    fn from(diagnostic: Diagnostic) -> Self {
+        Diagnostics(vec![diagnostic])
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "error: {}", self.message)?;
+        match self.span {
+            Some(span) => {
+                let gutter = " ".repeat(span.line.to_string().len());
+                writeln!(f, "{}--> {}:{}:{}", gutter, self.file, span.line, span.column)?;
+                if let Some(snippet) = &self.snippet {
+                    writeln!(f, "{} |", gutter)?;
+                    writeln!(f, "{} | {}", span.line, snippet)?;
+                    let marker_offset = snippet.get(..span.column - 1).map(|s| s.chars().count()).unwrap_or(0);
+                    writeln!(f, "{} | {}{}", gutter, " ".repeat(marker_offset), "^".repeat(span.len.max(1)))?;
+                }
+                if let Some(help) = &self.help {
+                    writeln!(f, "{} = help: {}", gutter, help)?;
+                }
+            }
+            None => {
+                writeln!(f, " --> {}", self.file)?;
+                if let Some(help) = &self.help {
+                    writeln!(f, "  = help: {}", help)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Display for Diagnostics {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for diagnostic in &self.0 {
+            writeln!(f, "{}", diagnostic)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,268 @@
+use serde_yaml::{Mapping, Value};
+use crate::backend::yaml::include::{origin_of, Origin};
+use crate::backend::yaml::source_map::{Segment, YamlPath};
+
+/// An expanded template with the origins of its parts.
+pub struct Layer {
+    pub value: Value,
+    pub origins: Vec<Origin>,
+}
+
+/// A `remove` of the variant that could not be applied, at its position in the file it is in.
+pub struct Problem {
+    pub file: usize,
+    pub path: YamlPath,
+    pub message: String,
+    pub help: String,
+}
+
+#[derive(Clone, Copy)]
+enum Side {
+    Base,
+    Variant,
+}
+
+/// Merges the template that `extends` another one over its base.
+///
+/// Children are matched by name: a child only the variant has is added at the end, a child
+/// both have keeps its place and takes the settings of the variant, with the `children` of
+/// both merged the same way. Naming a child without settings keeps the one of the base, and
+/// `remove: [Name]` drops children of the base. Every other key of the variant replaces the
+/// one of the base.
+///
+/// ```yaml
+/// extends: base.yaml
+/// project:
+///   default_location: "~/clients/acme"
+///   remove: [Podcast]
+///   children:
+///     - Output:
+///         options: [Ableton]
+///     - Contracts
+/// ```
+pub fn merge(base: Layer, variant: Layer) -> (Layer, Vec<Problem>) {
+    let mut merge = Merge { base: &base.origins, variant: &variant.origins, origins: Vec::new(), problems: Vec::new() };
+    let value = match (&base.value, &variant.value) {
+        (Value::Mapping(base_mapping), Value::Mapping(variant_mapping)) => Value::Mapping(merge.template(base_mapping, variant_mapping)),
+        _ => {
+            // the validator explains what is wrong with a template that is not a mapping
+            merge.place(Side::Variant, &[], &[]);
+            variant.value.clone()
+        }
+    };
+    let (origins, problems) = (merge.origins, merge.problems);
+    (Layer { value, origins }, problems)
+}
+
+struct Merge<'a> {
+    base: &'a [Origin],
+    variant: &'a [Origin],
+    origins: Vec<Origin>,
+    problems: Vec<Problem>,
+}
+
+impl Merge<'_> {
+    /// The yaml at `to` comes from `from` of `side`, with everything below it.
+    fn place(&mut self, side: Side, from: &[Segment], to: &[Segment]) {
+        self.point(side, from, to);
+        let origins = match side {
+            Side::Base => self.base,
+            Side::Variant => self.variant,
+        };
+        for origin in origins.iter().filter(|o| o.at.len() > from.len() && o.at.starts_with(from)) {
+            let mut at = to.to_vec();
+            at.extend_from_slice(&origin.at[from.len()..]);
+            self.origins.push(Origin { at, file: origin.file, path: origin.path.clone() });
+        }
+    }
+
+    /// The yaml at `to` comes from `from` of `side`, what is below it is placed on its own.
+    fn point(&mut self, side: Side, from: &[Segment], to: &[Segment]) {
+        let origins = match side {
+            Side::Base => self.base,
+            Side::Variant => self.variant,
+        };
+        let (file, path) = origin_of(origins, from).unwrap_or_default();
+        self.origins.push(Origin { at: to.to_vec(), file, path });
+    }
+
+    fn problem(&mut self, variant_at: &[Segment], message: impl Into<String>, help: impl Into<String>) {
+        let (file, path) = origin_of(self.variant, variant_at).unwrap_or_default();
+        self.problems.push(Problem { file, path, message: message.into(), help: help.into() });
+    }
+
+    fn template(&mut self, base: &Mapping, variant: &Mapping) -> Mapping {
+        self.point(Side::Variant, &[], &[]);
+        let mut merged = Mapping::new();
+        for (name, base_value) in base {
+            let at = [key(name)];
+            match (name.as_str(), base_value, variant.get(name)) {
+                (Some("project"), Value::Mapping(base_project), Some(Value::Mapping(variant_project))) => {
+                    let project = self.node(base_project, variant_project, &at, &at, &at);
+                    merged.insert(name.clone(), Value::Mapping(project));
+                }
+                (_, _, Some(variant_value)) => {
+                    self.place(Side::Variant, &at, &at);
+                    merged.insert(name.clone(), variant_value.clone());
+                }
+                (_, _, None) => {
+                    self.place(Side::Base, &at, &at);
+                    merged.insert(name.clone(), base_value.clone());
+                }
+            }
+        }
+        for (name, variant_value) in variant.iter().filter(|(name, _)| !base.contains_key(*name)) {
+            let at = [key(name)];
+            self.place(Side::Variant, &at, &at);
+            merged.insert(name.clone(), variant_value.clone());
+        }
+        merged
+    }
+
+    fn node(&mut self, base: &Mapping, variant: &Mapping, base_at: &[Segment], variant_at: &[Segment], at: &[Segment]) -> Mapping {
+        self.point(Side::Variant, variant_at, at);
+        let removed = self.removed(variant, variant_at);
+        let mut merged = Mapping::new();
+        for (name, base_value) in base {
+            let (from_base, from_variant, to) = (child(base_at, key(name)), child(variant_at, key(name)), child(at, key(name)));
+            match (name.as_str(), variant.get(name)) {
+                (Some("children"), variant_children) => {
+                    let children = self.children(base_value, variant_children, &removed, &from_base, &from_variant, &to);
+                    merged.insert(name.clone(), children);
+                }
+                (_, Some(variant_value)) => {
+                    self.place(Side::Variant, &from_variant, &to);
+                    merged.insert(name.clone(), variant_value.clone());
+                }
+                (_, None) => {
+                    self.place(Side::Base, &from_base, &to);
+                    merged.insert(name.clone(), base_value.clone());
+                }
+            }
+        }
+        for (name, variant_value) in variant.iter().filter(|(name, _)| !base.contains_key(*name) && name.as_str() != Some("remove")) {
+            self.place(Side::Variant, &child(variant_at, key(name)), &child(at, key(name)));
+            merged.insert(name.clone(), variant_value.clone());
+        }
+
+        let base_children = base.get("children").and_then(Value::as_sequence);
+        for (name, remove_at) in removed {
+            if !base_children.is_some_and(|children| children.iter().any(|c| entry_name(c) == Some(name.as_str()))) {
+                self.problem(&remove_at, format!("the base template has no child `{}` to remove here", name),
+                             "`remove` names children of the same node in the base template");
+            }
+        }
+        merged
+    }
+
+    fn children(&mut self, base: &Value, variant: Option<&Value>, removed: &[(String, YamlPath)],
+                base_at: &[Segment], variant_at: &[Segment], at: &[Segment]) -> Value {
+        let empty = Vec::new();
+        let variant_items = match variant {
+            Some(variant) => variant.as_sequence(),
+            None => Some(&empty),
+        };
+        let (Some(base_items), Some(variant_items)) = (base.as_sequence(), variant_items) else {
+            // not lists, the validator reports them
+            return match variant {
+                Some(variant) => {
+                    self.place(Side::Variant, variant_at, at);
+                    variant.clone()
+                }
+                None => {
+                    self.place(Side::Base, base_at, at);
+                    base.clone()
+                }
+            };
+        };
+        match variant {
+            Some(_) => self.point(Side::Variant, variant_at, at),
+            None => self.point(Side::Base, base_at, at),
+        }
+
+        let mut merged = Vec::new();
+        let mut used = vec![false; variant_items.len()];
+        for (i, entry) in base_items.iter().enumerate() {
+            let name = entry_name(entry);
+            if name.is_some_and(|name| removed.iter().any(|(r, _)| r == name)) {
+                continue;
+            }
+            let from_base = child(base_at, Segment::Index(i));
+            let to = child(at, Segment::Index(merged.len()));
+            let same = name.and_then(|name| (0..variant_items.len()).find(|j| !used[*j] && entry_name(&variant_items[*j]) == Some(name)));
+            let Some(j) = same else {
+                self.place(Side::Base, &from_base, &to);
+                merged.push(entry.clone());
+                continue;
+            };
+            used[j] = true;
+            let from_variant = child(variant_at, Segment::Index(j));
+            match (body(entry), body(&variant_items[j])) {
+                (Some(Value::Mapping(base_body)), Some(Value::Mapping(variant_body))) => {
+                    let name = name.unwrap_or_default();
+                    self.point(Side::Variant, &from_variant, &to);
+                    let merged_body = self.node(base_body, variant_body, &child(&from_base, key_str(name)),
+                                                &child(&from_variant, key_str(name)), &child(&to, key_str(name)));
+                    let mut merged_entry = Mapping::new();
+                    merged_entry.insert(Value::String(name.to_string()), Value::Mapping(merged_body));
+                    merged.push(Value::Mapping(merged_entry));
+                }
+                // naming a child without settings keeps the one of the base
+                (_, None | Some(Value::Null)) => {
+                    self.place(Side::Base, &from_base, &to);
+                    merged.push(entry.clone());
+                }
+                _ => {
+                    self.place(Side::Variant, &from_variant, &to);
+                    merged.push(variant_items[j].clone());
+                }
+            }
+        }
+        for (j, entry) in variant_items.iter().enumerate().filter(|(j, _)| !used[*j]) {
+            self.place(Side::Variant, &child(variant_at, Segment::Index(j)), &child(at, Segment::Index(merged.len())));
+            merged.push(entry.clone());
+        }
+        Value::Sequence(merged)
+    }
+
+    /// The names in the `remove` list of a node of the variant.
+    fn removed(&mut self, variant: &Mapping, variant_at: &[Segment]) -> Vec<(String, YamlPath)> {
+        let Some(remove) = variant.get("remove") else { return Vec::new() };
+        let remove_at = child(variant_at, key_str("remove"));
+        let Some(items) = remove.as_sequence() else {
+            self.problem(&remove_at, "`remove` has to be a list of child names",
+                         "write `remove: [Stems]` to drop `Stems` of the base template");
+            return Vec::new();
+        };
+        items.iter().enumerate()
+            .filter_map(|(i, item)| Some((item.as_str()?.to_string(), child(&remove_at, Segment::Index(i)))))
+            .collect()
+    }
+}
+
+/// The name of a `children` entry, `- Name` or `- Name: {..}`.
+fn entry_name(entry: &Value) -> Option<&str> {
+    match entry {
+        Value::String(name) => Some(name),
+        Value::Mapping(mapping) if mapping.len() == 1 => mapping.keys().next()?.as_str(),
+        _ => None,
+    }
+}
+
+fn body(entry: &Value) -> Option<&Value> {
+    entry.as_mapping().filter(|m| m.len() == 1)?.values().next()
+}
+
+fn key(name: &Value) -> Segment {
+    Segment::Key(name.as_str().map(str::to_string).unwrap_or_default())
+}
+
+fn key_str(name: &str) -> Segment {
+    Segment::Key(name.to_string())
+}
+
+fn child(path: &[Segment], segment: Segment) -> YamlPath {
+    let mut path = path.to_vec();
+    path.push(segment);
+    path
+}
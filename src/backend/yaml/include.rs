@@ -0,0 +1,419 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use serde_yaml::{Mapping, Value};
+use crate::backend::yaml::diagnostic::{Diagnostic, Diagnostics};
+use crate::backend::yaml::extends::{merge, Layer};
+use crate::backend::yaml::reader::read_fragment;
+use crate::backend::yaml::source_map::{Segment, SourceMap, YamlPath};
+use crate::backend::yaml::validator::{serde_diagnostic, template_dir};
+
+/// The files an expanded template was put together from.
+///
+/// Every list entry that moved while expanding remembers the file and the position it came
+/// from, problems found in the expanded template point into the file that has them.
+#[derive(Debug)]
+pub struct Sources {
+    files: Vec<SourceFile>,
+    origins: Vec<Origin>,
+}
+
+#[derive(Debug)]
+struct SourceFile {
+    name: String,
+    // canonical if possible, so two spellings of a path load the file once
+    key: PathBuf,
+    source: String,
+    value: Value,
+    /// The directory of the file relative to the template directory.
+    dir: PathBuf,
+}
+
+/// The expanded yaml at `at` was read from `path` in the file `file`.
+#[derive(Debug)]
+pub struct Origin {
+    pub at: YamlPath,
+    pub file: usize,
+    pub path: YamlPath,
+}
+
+/// A position in one of the source files.
+#[derive(Clone, PartialEq)]
+struct Position {
+    file: usize,
+    path: YamlPath,
+}
+
+/// A `$ref: "other.yaml#/definitions/Name"` or `include: other.yaml` entry.
+struct Include<'a> {
+    kind: &'static str,
+    target: Option<&'a str>,
+}
+
+impl Sources {
+    /// A diagnostic for the expanded yaml at `at`, it points into the file the yaml came from.
+    pub fn diagnostic(&self, at: &[Segment], message: impl Into<String>, help: impl Into<String>) -> Diagnostic {
+        self.error(at, message).with_help(help)
+    }
+
+    /// Like [`Sources::diagnostic`] for problems that need no help, the message says it all.
+    pub fn error(&self, at: &[Segment], message: impl Into<String>) -> Diagnostic {
+        let (file, path) = self.origin(at);
+        let file = &self.files[file];
+        let source_map = SourceMap::new(&file.source);
+        let span = if path.is_empty() { None } else { source_map.span(&path) };
+        Diagnostic::new(&file.name, message, span, &source_map)
+    }
+
+    /// The file the text at `at` was read from and the line the text starts on in it.
+    pub fn text_line(&self, at: &[Segment]) -> Option<(&str, usize)> {
+        let (file, path) = self.origin(at);
+        let file = &self.files[file];
+        let line = SourceMap::new(&file.source).text_line(&path)?;
+        Some((&file.name, line))
+    }
+
+    fn origin(&self, at: &[Segment]) -> (usize, YamlPath) {
+        origin_of(&self.origins, at).unwrap_or_else(|| (0, at.to_vec()))
+    }
+}
+
+/// The file and the position in it the expanded yaml at `at` was read from.
+pub fn origin_of(origins: &[Origin], at: &[Segment]) -> Option<(usize, YamlPath)> {
+    let closest = origins.iter()
+        .filter(|origin| at.starts_with(&origin.at))
+        .max_by_key(|origin| origin.at.len())?;
+    let mut path = closest.path.clone();
+    path.extend_from_slice(&at[closest.at.len()..]);
+    Some((closest.file, path))
+}
+
+/// Replaces the `$ref` and `include` entries of a template with the fragments they point at.
+///
+/// ```yaml
+/// definitions:
+///   Mixdown:
+///     - Master
+///     - Stems
+/// project:
+///   default_location: "~/music"
+///   children:
+///     - $ref: "#/definitions/Mixdown"         # from this file
+///     - include: fragments/samples.yaml       # a whole file
+///     - $ref: "shared.yaml#/definitions/Art"  # from another file
+/// ```
+///
+/// A fragment that is a list is spliced into the list that includes it. Files are relative to
+/// the file that includes them, and so are the content files of the nodes they bring in.
+/// Problems are reported and the entry is left out, so the rest of the template is still checked.
+///
+/// A template that `extends: base.yaml` is merged over its expanded base, see [`merge`].
+/// `root` is where the template is in the file, the file can hold several named templates.
+pub fn expand(value: Value, source: &str, file: &str, root: &[Segment]) -> (Value, Sources, Diagnostics) {
+    let main = SourceFile {
+        name: file.to_string(),
+        key: file_key(Path::new(file)),
+        source: source.to_string(),
+        value,
+        dir: PathBuf::new(),
+    };
+    let mut expander = Expander {
+        base_dir: template_dir(file),
+        sources: Sources { files: vec![main], origins: Vec::new() },
+        diagnostics: Diagnostics::default(),
+        stack: Vec::new(),
+        extending: Vec::new(),
+    };
+    let layer = expander.document(0, root);
+    expander.sources.origins = layer.origins;
+    (layer.value, expander.sources, expander.diagnostics)
+}
+
+struct Expander {
+    base_dir: PathBuf,
+    sources: Sources,
+    diagnostics: Diagnostics,
+    /// The fragments being expanded, to find includes that include themselves.
+    stack: Vec<Position>,
+    /// The files whose bases are being read, to find templates that extend themselves.
+    extending: Vec<usize>,
+}
+
+impl Expander {
+    fn error(&mut self, position: &Position, message: impl Into<String>, help: impl Into<String>) {
+        let file = &self.sources.files[position.file];
+        let source_map = SourceMap::new(&file.source);
+        let diagnostic = Diagnostic::new(&file.name, message, source_map.span(&position.path), &source_map).with_help(help);
+        self.diagnostics.push(diagnostic);
+    }
+
+    /// The template at `root` in `file` with its includes expanded and merged over the template it extends.
+    fn document(&mut self, file: usize, root: &[Segment]) -> Layer {
+        let root = Position { file, path: root.to_vec() };
+        let mut value = value_at(&self.sources.files[file].value, root.path.iter()).cloned().unwrap_or_default();
+        let mut extends = None;
+        if let Value::Mapping(mapping) = &mut value {
+            // the definitions are only read through references
+            if take(mapping, "definitions").is_some_and(|d| !d.is_mapping()) {
+                self.error(&root.child(key("definitions")), "`definitions` has to be a mapping",
+                           "name every fragment below `definitions:`, for example `Mixdown:` followed by its children");
+            }
+            match take(mapping, "extends") {
+                Some(Value::String(base)) => extends = Some(base),
+                Some(_) => self.error(&root.child(key("extends")), "`extends` has to be the path of a template",
+                                      "write `extends: base.yaml`, the path is relative to this file"),
+                None => {}
+            }
+        }
+
+        self.stack = vec![root.clone()];
+        self.sources.origins = vec![Origin { at: Vec::new(), file, path: root.path.clone() }];
+        self.walk(&mut value, &[], &root);
+        let layer = Layer { value, origins: std::mem::take(&mut self.sources.origins) };
+
+        let Some(base) = extends else { return layer };
+        let at = root.child(key("extends"));
+        let Some(base_file) = self.load(&base, &at) else { return layer };
+        if base_file == file || self.extending.contains(&base_file) {
+            let start = self.extending.iter().position(|f| *f == base_file).unwrap_or(self.extending.len());
+            let chain: Vec<&str> = self.extending[start..].iter().chain([&file, &base_file])
+                .map(|f| self.sources.files[*f].name.as_str())
+                .collect();
+            let message = format!("circular extends {}", chain.join(" -> "));
+            self.error(&at, message, "a template cannot extend itself, directly or through its bases");
+            return layer;
+        }
+        self.extending.push(file);
+        let base_layer = self.document(base_file, &[]);
+        self.extending.pop();
+
+        let (merged, problems) = merge(base_layer, layer);
+        for problem in problems {
+            self.error(&Position { file: problem.file, path: problem.path }, problem.message, problem.help);
+        }
+        merged
+    }
+
+    fn walk(&mut self, value: &mut Value, at: &[Segment], position: &Position) {
+        if let Some(include) = value.as_mapping().and_then(include_of) {
+            // a whole node body or list can be a reference as well
+            match self.resolve(&include, position) {
+                Some((fragment, fragment_position)) => {
+                    self.sources.origins.push(Origin { at: at.to_vec(), file: fragment_position.file, path: fragment_position.path.clone() });
+                    *value = fragment;
+                    self.walk(value, at, &fragment_position);
+                    self.stack.pop();
+                }
+                None => *value = Value::Null,
+            }
+            return;
+        }
+
+        match value {
+            Value::Sequence(items) => {
+                let mut expanded = Vec::new();
+                for (i, item) in std::mem::take(items).into_iter().enumerate() {
+                    self.entry(item, at, position.child(Segment::Index(i)), &mut expanded);
+                }
+                *items = expanded;
+            }
+            Value::Mapping(mapping) => {
+                self.relocate_paths(mapping, position);
+                for (name, child) in mapping.iter_mut() {
+                    let Some(name) = name.as_str() else { continue };
+                    let mut child_at = at.to_vec();
+                    child_at.push(key(name));
+                    self.walk(child, &child_at, &position.child(key(name)));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Pushes the list entry `item` to `expanded`, or the entries of the fragment it includes.
+    fn entry(&mut self, item: Value, at: &[Segment], position: Position, expanded: &mut Vec<Value>) {
+        if let Some(include) = item.as_mapping().and_then(include_of) {
+            let Some((fragment, fragment_position)) = self.resolve(&include, &position) else { return };
+            match fragment {
+                Value::Sequence(items) => {
+                    for (j, item) in items.into_iter().enumerate() {
+                        self.entry(item, at, fragment_position.child(Segment::Index(j)), expanded);
+                    }
+                }
+                item => self.entry(item, at, fragment_position, expanded),
+            }
+            self.stack.pop();
+            return;
+        }
+
+        let mut item_at = at.to_vec();
+        item_at.push(Segment::Index(expanded.len()));
+        if position.file != 0 || position.path != item_at {
+            self.sources.origins.push(Origin { at: item_at.clone(), file: position.file, path: position.path.clone() });
+        }
+        let mut item = item;
+        self.walk(&mut item, &item_at, &position);
+        expanded.push(item);
+    }
+
+    /// The fragment `include` points at and where it is, the fragment is pushed on the stack.
+    fn resolve(&mut self, include: &Include, position: &Position) -> Option<(Value, Position)> {
+        let at = position.child(key(include.kind));
+        let Some(target) = include.target else {
+            self.error(&at, format!("`{}` has to be a text", include.kind),
+                       "write the file or the fragment to include, for example `$ref: \"#/definitions/Mixdown\"`");
+            return None;
+        };
+        let (file, pointer) = match target.split_once('#') {
+            Some((file, pointer)) => (file, pointer),
+            None => (target, ""),
+        };
+        let file = if file.is_empty() { position.file } else { self.load(file, &at)? };
+        if !pointer.is_empty() && !pointer.starts_with('/') {
+            self.error(&at, format!("`{}` is not a fragment path", target),
+                       "point at a definition, for example `#/definitions/Mixdown`");
+            return None;
+        }
+
+        let Some((path, fragment)) = lookup(&self.sources.files[file].value, pointer) else {
+            let name = &self.sources.files[file].name;
+            self.error(&at, format!("`{}` has no fragment at `#{}`", name, pointer),
+                       "fragments are usually named below `definitions:` at the top of the file");
+            return None;
+        };
+        let fragment = fragment.clone();
+        let target = Position { file, path };
+        if let Some(start) = self.stack.iter().position(|p| *p == target) {
+            let chain: Vec<String> = self.stack[start..].iter().chain([&target]).map(|p| self.describe(p)).collect();
+            self.error(&at, format!("include cycle {}", chain.join(" -> ")),
+                       "a fragment cannot include itself, directly or through other fragments");
+            return None;
+        }
+        self.stack.push(target.clone());
+        Some((fragment, target))
+    }
+
+    /// Reads the file at `relative` to the file `at` is in, it is only read once.
+    fn load(&mut self, relative: &str, at: &Position) -> Option<usize> {
+        let dir = self.sources.files[at.file].dir.join(relative);
+        let path = self.base_dir.join(&dir);
+        let file_key = file_key(&path);
+        if let Some(loaded) = self.sources.files.iter().position(|f| f.key == file_key) {
+            return Some(loaded);
+        }
+
+        let source = match read_fragment(&path) {
+            Ok(source) => source,
+            Err(e) => {
+                let message = match e.kind() {
+                    ErrorKind::NotFound => format!("the included file `{}` does not exist", relative),
+                    _ => format!("the included file `{}` cannot be read: {}", relative, e),
+                };
+                self.error(at, message, format!("paths are relative to the file that includes them, this one was looked up at `{}`", path.display()));
+                return None;
+            }
+        };
+        let name = path.to_string_lossy().into_owned();
+        let value = match serde_yaml::from_str(&source) {
+            Ok(value) => value,
+            Err(e) => {
+                self.diagnostics.push(serde_diagnostic(&e, &name, &SourceMap::new(&source)));
+                return None;
+            }
+        };
+        let dir = dir.parent().map(Path::to_path_buf).unwrap_or_default();
+        self.sources.files.push(SourceFile { name, key: file_key, source, value, dir });
+        Some(self.sources.files.len() - 1)
+    }
+
+    /// Makes the `path` of a file node and the `copy` of a node from an included file relative
+    /// to the template.
+    fn relocate_paths(&self, mapping: &mut Mapping, position: &Position) {
+        let dir = &self.sources.files[position.file].dir;
+        if dir.as_os_str().is_empty() {
+            return;
+        }
+        let field = match position.path.last() {
+            Some(segment) if *segment == key("file") => "path",
+            Some(segment) if *segment == key("copy") => "from",
+            _ => "copy",
+        };
+        if let Some(Value::String(path)) = mapping.get_mut(field) {
+            *path = dir.join(&*path).to_string_lossy().into_owned();
+        }
+    }
+
+    fn describe(&self, position: &Position) -> String {
+        let name = &self.sources.files[position.file].name;
+        if position.path.is_empty() {
+            return name.clone();
+        }
+        let pointer: Vec<String> = position.path.iter()
+            .map(|segment| match segment {
+                Segment::Key(k) => k.clone(),
+                Segment::Index(i) => i.to_string(),
+            })
+            .collect();
+        format!("{}#/{}", name, pointer.join("/"))
+    }
+}
+
+impl Position {
+    fn child(&self, segment: Segment) -> Position {
+        let mut path = self.path.clone();
+        path.push(segment);
+        Position { file: self.file, path }
+    }
+}
+
+/// The include of a single key mapping, `include` only counts with a file name since it
+/// is a common folder name as well.
+fn include_of(mapping: &Mapping) -> Option<Include<'_>> {
+    if mapping.len() != 1 {
+        return None;
+    }
+    let (name, target) = mapping.iter().next()?;
+    match name.as_str()? {
+        "$ref" => Some(Include { kind: "$ref", target: target.as_str() }),
+        "include" if target.is_string() => Some(Include { kind: "include", target: target.as_str() }),
+        _ => None,
+    }
+}
+
+/// Follows a `/definitions/Name` pointer, an empty pointer is the whole document.
+fn lookup<'a>(document: &'a Value, pointer: &str) -> Option<(YamlPath, &'a Value)> {
+    let mut path = Vec::new();
+    let mut value = document;
+    for step in pointer.split('/').skip(1) {
+        let segment = match value {
+            Value::Sequence(_) => Segment::Index(step.parse().ok()?),
+            _ => key(step),
+        };
+        value = value_at(value, [&segment])?;
+        path.push(segment);
+    }
+    Some((path, value))
+}
+
+/// The yaml at `path` below `value`.
+pub fn value_at<'a, 'p>(value: &'a Value, path: impl IntoIterator<Item = &'p Segment>) -> Option<&'a Value> {
+    path.into_iter().try_fold(value, |value, segment| match segment {
+        Segment::Key(name) => value.as_mapping()?.get(name.as_str()),
+        Segment::Index(i) => value.as_sequence()?.get(*i),
+    })
+}
+
+/// Removes `name` from a mapping without changing the order of the other keys.
+fn take(mapping: &mut Mapping, name: &str) -> Option<Value> {
+    let value = mapping.get(name).cloned();
+    mapping.retain(|k, _| k.as_str() != Some(name));
+    value
+}
+
+fn file_key(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn key(name: &str) -> Segment {
+    Segment::Key(name.to_string())
+}
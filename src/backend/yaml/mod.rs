@@ -1,12 +1,48 @@
+use std::path::Path;
 use slab_tree::Tree;
 use crate::backend::tree::nodes::LeafNodeType;
 use crate::backend::yaml::parser::parse_project_yaml;
 use crate::backend::yaml::reader::read_file;
+use crate::backend::yaml::validator::check_template;
+use crate::backend::yaml::diagnostic::Diagnostics;
+use crate::error::Result;
 
+mod catalog;
+pub mod diagnostic;
+mod extends;
+mod include;
+pub mod model;
 mod parser;
 mod reader;
+mod references;
+pub mod source_map;
+mod validator;
 
-pub fn parse_yaml(path: &str) -> Tree<LeafNodeType> {
-    let yaml_str = read_file(path);
-    parse_project_yaml(yaml_str.as_str())
+pub use catalog::{discover_templates, find_template, list_templates, usable_templates, visible_templates, TemplateEntry, TemplateSearch, TEMPLATE_ENV};
+pub use parser::{build_tree, Settings};
+
+/// Reads and validates the template at `path`, with its settings.
+pub fn load_yaml(path: &Path) -> Result<(Tree<LeafNodeType>, Settings)> {
+    let yaml_str = read_file(path)?;
+    load_yaml_str(yaml_str.as_str(), &path.to_string_lossy())
+}
+
+/// Like [`load_yaml`] for a template that is already read, `file` is only used in error messages.
+pub fn load_yaml_str(yaml_str: &str, file: &str) -> Result<(Tree<LeafNodeType>, Settings)> {
+    Ok(parse_project_yaml(yaml_str, file, None)?)
+}
+
+/// Like [`load_yaml`] for one of the templates found by [`list_templates`].
+pub fn load_template_entry(entry: &TemplateEntry) -> Result<(Tree<LeafNodeType>, Settings)> {
+    let yaml_str = read_file(&entry.file)?;
+    Ok(parse_project_yaml(yaml_str.as_str(), &entry.file.to_string_lossy(), entry.key.as_deref())?)
+}
+
+/// The template with its includes and the templates it extends merged in, as yaml.
+pub fn merged_yaml(entry: &TemplateEntry) -> Result<String> {
+    let yaml_str = read_file(&entry.file)?;
+    let (value, sources) = check_template(&yaml_str, &entry.file.to_string_lossy(), entry.key.as_deref())?;
+    let merged = serde_yaml::to_string(&value)
+        .map_err(|e| Diagnostics::from(sources.diagnostic(&[], e.to_string(), "the merged template cannot be written as yaml")))?;
+    Ok(merged)
 }
@@ -0,0 +1,480 @@
+use std::fmt;
+use std::marker::PhantomData;
+use serde::de::value::{MapAccessDeserializer, SeqAccessDeserializer};
+use serde::de::{self, IntoDeserializer, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use crate::backend::folder::ConflictPolicy;
+use crate::backend::tree::transform::Transform;
+
+/// The template format version understood by this build of scaffolder.
+pub const CURRENT_VERSION: u32 = 1;
+
+/// A whole template file.
+///
+/// ```yaml
+/// version: 1
+/// project:
+///   default_location: "~/music"
+///   children:
+///     - ProjectName:
+///         options: ""
+/// ```
+///
+/// `definitions` with the `$ref`/`include` entries pointing at them and the base template
+/// of `extends` are merged in before the template is deserialized, so they never show up here.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields, expecting = "a template with a `project` section")]
+pub struct Template {
+    #[serde(default = "default_version")]
+    pub version: u32,
+    /// Shown next to the name when picking one of several templates.
+    #[serde(default)]
+    pub description: Option<String>,
+    pub project: Project,
+}
+
+/// The `project` section, it becomes the `Location` root of the tree.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields, expecting = "the `project` settings, like `default_location` and `children`")]
+pub struct Project {
+    pub default_location: String,
+    /// What to do when the project already exists on disk, `abort`, `merge`, `suffix` or `prompt`.
+    #[serde(default)]
+    pub on_conflict: Option<ConflictPolicy>,
+    #[serde(default)]
+    pub children: Option<Vec<Child>>,
+    #[serde(default, deserialize_with = "choices")]
+    pub childoptions: Option<Vec<String>>,
+    /// Shell commands run in the project folder once everything is built, in order.
+    #[serde(default)]
+    pub hooks: Vec<String>,
+}
+
+/// An entry of a `children` list.
+///
+/// Either a plain folder name (`- Master`) or a single key mapping
+/// from the node name to its [`NodeSpec`] (`- Output: {children: [..]}`).
+#[derive(Debug)]
+pub enum Child {
+    Leaf(String),
+    Node { name: String, spec: NodeSpec },
+}
+
+/// The body of a named node, an empty body (`- Stems:`) is a plain folder.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, expecting = "the settings of a node, like `options` or `children`")]
+pub struct NodeSpec {
+    #[serde(default)]
+    pub options: Option<Options>,
+    #[serde(default)]
+    pub children: Option<Vec<Child>>,
+    #[serde(default, deserialize_with = "choices")]
+    pub childoptions: Option<Vec<String>>,
+    /// Lets the user check several choices, every checked choice becomes its own folder.
+    #[serde(default)]
+    pub multiselect: bool,
+    /// Only creates the node and its children for some answers of other nodes.
+    #[serde(default)]
+    pub when: Option<When>,
+    /// Makes the node a file instead of a folder, `file:` without a body is an empty file.
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub file: Option<Box<FileSpec>>,
+    /// Checks the answer of a text input has to pass.
+    #[serde(default)]
+    pub rules: Option<Box<RulesSpec>>,
+    /// Rewrites the answer of a text input before it becomes a name.
+    #[serde(default)]
+    pub transform: Option<Transforms>,
+    /// Makes the node a copy of a file or folder next to the template.
+    #[serde(default)]
+    pub copy: Option<Box<CopySpec>>,
+    /// Makes the node a link pointing at the target, `{{Name}}` placeholders are replaced.
+    #[serde(default)]
+    pub symlink: Option<String>,
+}
+
+/// The body of a file node, empty if neither `content` nor `path` is given.
+///
+/// ```yaml
+/// - README.md:
+///     file:
+///       content: "# My project"
+/// - .gitignore:
+///     file:
+///       path: files/gitignore   # relative to the template
+/// - session-notes.md:
+///     file:
+///       path: files/session-notes.md
+///       render: true            # a minijinja template filled in with the answers
+/// - run.sh:
+///     file:
+///       content: "#!/bin/sh"
+///       mode: 755               # octal, like chmod
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, expecting = "the `content`, `path`, `render` or `mode` of a file")]
+pub struct FileSpec {
+    #[serde(default)]
+    pub content: Option<String>,
+    #[serde(default)]
+    pub path: Option<String>,
+    #[serde(default)]
+    pub render: bool,
+    #[serde(default)]
+    pub mode: Option<Mode>,
+}
+
+/// The unix permissions of a file, written as octal digits (`755`, `"0644"` or `"0o600"`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mode(pub u32);
+
+/// The `copy` of a node, a file or a folder with everything in it.
+///
+/// ```yaml
+/// - "{{ProjectName}}.als":
+///     copy: assets/ableton_template.als   # relative to the template
+/// - Artwork:
+///     copy:
+///       from: assets/artwork
+///       render_names: true                # `{{ProjectName}}` in the copied names is replaced
+/// ```
+#[derive(Debug)]
+pub enum CopySpec {
+    From(String),
+    Full(CopyFrom),
+}
+
+/// The long form of [`CopySpec`].
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields, expecting = "the path to copy `from`, and `render_names`")]
+pub struct CopyFrom {
+    pub from: String,
+    #[serde(default)]
+    pub render_names: bool,
+}
+
+impl CopySpec {
+    pub fn from(&self) -> &str {
+        match self {
+            CopySpec::From(from) | CopySpec::Full(CopyFrom { from, .. }) => from,
+        }
+    }
+
+    pub fn render_names(&self) -> bool {
+        matches!(self, CopySpec::Full(CopyFrom { render_names: true, .. }))
+    }
+}
+
+/// The `rules` of a text input.
+///
+/// ```yaml
+/// - ProjectName:
+///     options: ""
+///     rules:
+///       required: true
+///       pattern: "[A-Z].*"      # has to match the whole answer
+///       min_length: 2
+///       max_length: 40
+///       charset: "a-zA-Z0-9 _-"
+///       path_component: true    # no `/`, `..` or other characters folder names cannot have
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, expecting = "rules like `required` or `pattern`")]
+pub struct RulesSpec {
+    #[serde(default)]
+    pub required: bool,
+    #[serde(default)]
+    pub pattern: Option<String>,
+    #[serde(default)]
+    pub min_length: Option<usize>,
+    #[serde(default)]
+    pub max_length: Option<usize>,
+    #[serde(default)]
+    pub charset: Option<String>,
+    #[serde(default)]
+    pub path_component: bool,
+}
+
+/// The `transform` of a text input, one transform or a list applied in order.
+///
+/// ```yaml
+/// - ProjectName:
+///     options: ""
+///     transform: slug          # "My New Song " is built as `my-new-song`
+/// - SetName:
+///     options: ""
+///     transform: [trim, replace-spaces]
+/// ```
+#[derive(Debug, Default)]
+pub struct Transforms(pub Vec<Transform>);
+
+/// The `when` of a node, it maps node names to the answers they need to have.
+///
+/// ```yaml
+/// - Stems:
+///     when:
+///       ProjectType: Song           # a single answer
+///       Output: [Ableton, Fl Studio] # or one of a list
+/// ```
+#[derive(Debug, Default)]
+pub struct When(pub Vec<(String, Vec<String>)>);
+
+/// The `options` of a node.
+///
+/// A list lets the user pick one of the items, a string asks for a text input
+/// with the string as default value. A choice can have its own `children`, they
+/// are only built when it is picked:
+///
+/// ```yaml
+/// options:
+///   - Song
+///   - Podcast:
+///       children:
+///         - Episodes
+/// ```
+#[derive(Debug)]
+pub enum Options {
+    List(Vec<Child>),
+    Input(String),
+}
+
+fn default_version() -> u32 {
+    CURRENT_VERSION
+}
+
+/// Deserializes a present but empty key (`key:`) as the default value instead of `None`.
+fn null_as_default<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Ok(Some(Option::<T>::deserialize(deserializer)?.unwrap_or_default()))
+}
+
+const NO_CHOICES: &str = "the list of choices is empty, add at least one choice or remove the key";
+
+/// Deserializes `childoptions`, a list that has to have at least one choice.
+fn choices<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vec<String>>, D::Error> {
+    let choices = Vec::<String>::deserialize(deserializer)?;
+    if choices.is_empty() {
+        return Err(de::Error::custom(NO_CHOICES));
+    }
+    Ok(Some(choices))
+}
+
+impl Child {
+    pub fn name(&self) -> &str {
+        match self {
+            Child::Leaf(name) | Child::Node { name, .. } => name,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for When {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct WhenVisitor;
+
+        impl<'de> Visitor<'de> for WhenVisitor {
+            type Value = When;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a mapping from node names to answers")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<When, A::Error> {
+                let mut entries = Vec::new();
+                while let Some((key, Answers(answers))) = map.next_entry::<String, Answers>()? {
+                    entries.push((key, answers));
+                }
+                Ok(When(entries))
+            }
+        }
+
+        deserializer.deserialize_map(WhenVisitor)
+    }
+}
+
+/// The answers of a `when` entry.
+struct Answers(Vec<String>);
+
+impl<'de> Deserialize<'de> for Answers {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        one_or_many(deserializer, "an answer or a list of answers").map(Answers)
+    }
+}
+
+/// Deserializes a single value written on its own or a list of them, like the answers in a `when`.
+fn one_or_many<'de, D, T>(deserializer: D, expecting: &'static str) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    struct OneOrManyVisitor<T> {
+        expecting: &'static str,
+        item: PhantomData<T>,
+    }
+
+    impl<'de, T: Deserialize<'de>> Visitor<'de> for OneOrManyVisitor<T> {
+        type Value = Vec<T>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "{}", self.expecting)
+        }
+
+        fn visit_str<E: de::Error>(self, text: &str) -> Result<Vec<T>, E> {
+            T::deserialize(text.into_deserializer()).map(|item| vec![item])
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Vec<T>, A::Error> {
+            let items: Vec<Text<T>> = Vec::deserialize(SeqAccessDeserializer::new(seq))?;
+            Ok(items.into_iter().map(|Text(item)| item).collect())
+        }
+    }
+
+    deserializer.deserialize_any(OneOrManyVisitor { expecting, item: PhantomData })
+}
+
+/// An item of [`one_or_many`], read from a text so names of unit enums get the same errors as texts.
+struct Text<T>(T);
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Text<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct TextVisitor<T>(PhantomData<T>);
+
+        impl<'de, T: Deserialize<'de>> Visitor<'de> for TextVisitor<T> {
+            type Value = Text<T>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a text")
+            }
+
+            fn visit_str<E: de::Error>(self, text: &str) -> Result<Text<T>, E> {
+                T::deserialize(text.into_deserializer()).map(Text)
+            }
+        }
+
+        deserializer.deserialize_any(TextVisitor(PhantomData))
+    }
+}
+
+// the enums below are written out instead of `#[serde(untagged)]`, untagged enums only
+// say that no variant matched and lose the position of the problem inside the value
+
+impl<'de> Deserialize<'de> for Options {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct OptionsVisitor;
+
+        impl<'de> Visitor<'de> for OptionsVisitor {
+            type Value = Options;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a list of choices or the default text of an input")
+            }
+
+            fn visit_str<E: de::Error>(self, default: &str) -> Result<Options, E> {
+                Ok(Options::Input(default.to_string()))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Options, A::Error> {
+                let choices: Vec<Child> = Vec::deserialize(SeqAccessDeserializer::new(seq))?;
+                if choices.is_empty() {
+                    return Err(de::Error::custom(NO_CHOICES));
+                }
+                Ok(Options::List(choices))
+            }
+        }
+
+        deserializer.deserialize_any(OptionsVisitor)
+    }
+}
+
+impl<'de> Deserialize<'de> for Mode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ModeVisitor;
+
+        impl Visitor<'_> for ModeVisitor {
+            type Value = Mode;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "octal permissions like `755`")
+            }
+
+            // yaml reads `755` as a decimal number, its digits are the octal mode
+            fn visit_u64<E: de::Error>(self, mode: u64) -> Result<Mode, E> {
+                self.visit_str(&mode.to_string())
+            }
+
+            fn visit_str<E: de::Error>(self, mode: &str) -> Result<Mode, E> {
+                let digits = mode.strip_prefix("0o").unwrap_or(mode);
+                match u32::from_str_radix(digits, 8) {
+                    Ok(mode) if !digits.starts_with('+') && mode <= 0o7777 => Ok(Mode(mode)),
+                    _ => Err(de::Error::custom(format!("`{}` is not a file mode, write octal permissions like `755` or `644`", mode))),
+                }
+            }
+        }
+
+        deserializer.deserialize_any(ModeVisitor)
+    }
+}
+
+impl<'de> Deserialize<'de> for Transforms {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        one_or_many(deserializer, "the name of a transform or a list of them").map(Transforms)
+    }
+}
+
+impl<'de> Deserialize<'de> for CopySpec {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct CopyVisitor;
+
+        impl<'de> Visitor<'de> for CopyVisitor {
+            type Value = CopySpec;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "the path to copy or a mapping with `from`")
+            }
+
+            fn visit_str<E: de::Error>(self, from: &str) -> Result<CopySpec, E> {
+                Ok(CopySpec::From(from.to_string()))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<CopySpec, A::Error> {
+                CopyFrom::deserialize(MapAccessDeserializer::new(map)).map(CopySpec::Full)
+            }
+        }
+
+        deserializer.deserialize_any(CopyVisitor)
+    }
+}
+
+impl<'de> Deserialize<'de> for Child {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ChildVisitor;
+
+        impl<'de> Visitor<'de> for ChildVisitor {
+            type Value = Child;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a folder name or a single `Name: {{...}}` mapping")
+            }
+
+            fn visit_str<E: de::Error>(self, name: &str) -> Result<Child, E> {
+                Ok(Child::Leaf(name.to_string()))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Child, A::Error> {
+                let (name, spec) = map.next_entry::<String, Option<NodeSpec>>()?
+                    .ok_or_else(|| de::Error::custom("a child mapping needs a name"))?;
+                if let Some(other) = map.next_key::<String>()? {
+                    return Err(de::Error::custom(format!(
+                        "child `{}` has a sibling key `{}` in the same entry, start a new entry with `- {}:` or indent it under `{}`",
+                        name, other, other, name
+                    )));
+                }
+                Ok(Child::Node { name, spec: spec.unwrap_or_default() })
+            }
+        }
+
+        deserializer.deserialize_any(ChildVisitor)
+    }
+}
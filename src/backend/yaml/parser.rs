@@ -1,101 +1,328 @@
-use serde::Deserialize;
-use serde_yaml::{Mapping, Sequence, Value};
 use slab_tree::*;
 use slab_tree::NodeMut;
 use crate::backend;
-use backend::tree::nodes::LeafNodeType;
-use crate::front_end::ui::ui::StatefulList;
-
-
-pub fn parse_project_yaml(yaml_str: &str) -> Tree<LeafNodeType> {
-    let de = serde_yaml::Deserializer::from_str(yaml_str);
-    let value = Value::deserialize(de).expect("error while deserialzing");
-    let mapping = value.as_mapping().expect("No Mapping");
-    let project = mapping.get("project").map(|p| p.as_mapping()).flatten().expect("No Project");
-    let default_location = project.get("default_location")
-        .map(|l| l.as_str())
-        .flatten()
-        .expect("no value for default_location.\nplease provide a default_location in the template.yaml file");
+use std::path::Path;
+use backend::folder::ConflictPolicy;
+use backend::tree::condition::Condition;
+use backend::tree::nodes::{FileContent, LeafNodeType, SourceLine};
+use backend::tree::rules::Rules;
+use backend::tree::transform::Transform;
+use backend::yaml::diagnostic::Diagnostics;
+use backend::yaml::include::Sources;
+use backend::yaml::model::{Child, NodeSpec, Options, RulesSpec, Template, Transforms, When};
+use backend::yaml::source_map::{Segment, YamlPath};
+use backend::yaml::validator::{parse_template, template_dir};
+use crate::backend::tree::options::StatefulList;
+
+
+/// How the answers of a template are built, next to its questions.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Settings {
+    /// Left to the caller when the template does not set it.
+    pub on_conflict: Option<ConflictPolicy>,
+    /// Commands run in the project folder after the build.
+    pub hooks: Vec<String>,
+}
+
+/// The question tree and the settings of a template, see [`parse_template`] for `name`.
+pub fn parse_project_yaml(yaml_str: &str, file: &str, name: Option<&str>) -> Result<(Tree<LeafNodeType>, Settings), Diagnostics> {
+    let (template, sources) = parse_template(yaml_str, file, name)?;
+    let files = Files { base_dir: &template_dir(file), sources: Some(&sources) };
+    Ok((walk_template(&template, &files), settings(&template)))
+}
+
+fn settings(template: &Template) -> Settings {
+    Settings {
+        on_conflict: template.project.on_conflict,
+        hooks: template.project.hooks.clone(),
+    }
+}
+
+/// Turns the validated template into the tree of questions shown by the ui.
+///
+/// Content paths of file nodes and copied assets are resolved against `base_dir`.
+pub fn build_tree(template: &Template, base_dir: &Path) -> Tree<LeafNodeType> {
+    walk_template(template, &Files { base_dir, sources: None })
+}
+
+/// Where the template was read from.
+struct Files<'a> {
+    base_dir: &'a Path,
+    /// Lets render errors of inline content point into the template.
+    sources: Option<&'a Sources>,
+}
+
+impl Files<'_> {
+    fn text_line(&self, at: &[Segment]) -> Option<SourceLine> {
+        let (file, line) = self.sources?.text_line(at)?;
+        Some(SourceLine { file: file.to_string(), line })
+    }
+}
+
+fn walk_template(template: &Template, files: &Files) -> Tree<LeafNodeType> {
+    let project = &template.project;
     let root_node = LeafNodeType::TextInput {
         name: "Location".to_string(),
-        input: default_location.to_string(),
+        input: project.default_location.clone(),
+        rules: Rules::default(),
+        transform: Vec::new(),
+        when: None,
     };
     let mut tree = TreeBuilder::new().with_root(root_node).build();
-    walk_project(project, &mut tree.root_mut().unwrap());
+    let root = Parent { path: "", choice: None, at: vec![Segment::Key(String::from("project"))] };
+    walk_project(&project.children, &project.childoptions, files, &mut tree.root_mut().unwrap(), &root);
 
     tree
 }
 
-fn walk_project(project: &Mapping, parent: &mut NodeMut<LeafNodeType>) {
-    let children_opt = project.get("children")
-        .map(|c| c.as_sequence())
-        .flatten();
-
-    let child_options = project.get("childoptions")
-        .map(|o| o.as_sequence())
-        .flatten()
-        .map(|s| get_options(s));
+/// The node visited children are appended to.
+struct Parent<'a> {
+    /// The node path of the parent, empty for the root.
+    path: &'a str,
+    /// The choice of the parent option the children belong to, for per-option subtrees.
+    choice: Option<&'a str>,
+    /// Where the body of the parent is in the template.
+    at: YamlPath,
+}
 
-    if let Some(children) = children_opt {
-        visit_children(children, parent, child_options);
+fn walk_project(children: &Option<Vec<Child>>, child_options: &Option<Vec<String>>, files: &Files, parent_node: &mut NodeMut<LeafNodeType>, parent: &Parent) {
+    if let Some(children) = children {
+        visit_children(children, parent_node, child_options, files, parent);
     }
 }
 
 
-fn visit_children(children: &Sequence, parent: &mut NodeMut<LeafNodeType>, child_options: Option<Vec<String>>) {
-    for child in children {
-        if let Some(child_as_map) = child.as_mapping() {
-            let (node_name, children) = child_as_map.iter().next()
-                .expect("Warum kein child. WTF");
-            if let Some(key) = node_name.as_str() {
-                if let Some(value_of_child_as_mapping) = children.as_mapping() {
-                    let node_type = get_node_type(value_of_child_as_mapping, key, &child_options);
-                    let mut node = parent.append(node_type);
-                    walk_project(value_of_child_as_mapping, &mut node);
+fn visit_children(children: &[Child], parent_node: &mut NodeMut<LeafNodeType>, child_options: &Option<Vec<String>>, files: &Files, parent: &Parent) {
+    for (i, child) in children.iter().enumerate() {
+        let at = yaml_path(&parent.at, &[key("children"), Segment::Index(i), key(child.name())]);
+        match child {
+            // files have a fixed name, childoptions of the parent do not apply to them
+            Child::Node { name, spec: spec @ NodeSpec { file: Some(file), .. } } => {
+                let content = match (&file.content, &file.path) {
+                    (Some(content), _) => FileContent::Inline(content.clone()),
+                    (None, Some(path)) => FileContent::Path(absolute(&files.base_dir.join(path))),
+                    (None, None) => FileContent::Empty,
+                };
+                let content = match file.render {
+                    true => {
+                        let origin = file.content.as_ref().and_then(|_| files.text_line(&yaml_path(&at, &[key("file"), key("content")])));
+                        FileContent::Template(Box::new(content), origin)
+                    }
+                    false => content,
+                };
+                let when = with_choice(condition(&spec.when), parent);
+                let mode = file.mode.map(|mode| mode.0);
+                parent_node.append(LeafNodeType::File { name: name.clone(), content, mode, when });
+            }
+            Child::Node { name, spec: spec @ NodeSpec { copy: Some(copy), .. } } => {
+                let content = FileContent::Copy { from: absolute(&files.base_dir.join(copy.from())), render_names: copy.render_names() };
+                let when = with_choice(condition(&spec.when), parent);
+                parent_node.append(LeafNodeType::File { name: name.clone(), content, mode: None, when });
+            }
+            // the target is kept as written, a relative link points next to itself
+            Child::Node { name, spec: spec @ NodeSpec { symlink: Some(target), .. } } => {
+                let content = FileContent::Symlink(target.into());
+                let when = with_choice(condition(&spec.when), parent);
+                parent_node.append(LeafNodeType::File { name: name.clone(), content, mode: None, when });
+            }
+            Child::Node { name, spec } => {
+                let node_type = get_node_type(spec, name, child_options, with_choice(condition(&spec.when), parent));
+                let mut node = parent_node.append(node_type);
+                let path = child_path(parent.path, name);
+                walk_project(&spec.children, &spec.childoptions, files, &mut node, &Parent { path: &path, choice: None, at: at.clone() });
+
+                // the children of a choice are only built when it is selected
+                if let Some(Options::List(items)) = &spec.options {
+                    for (j, item) in items.iter().enumerate() {
+                        if let Child::Node { name: choice, spec: choice_spec } = item {
+                            let choice_at = yaml_path(&at, &[key("options"), Segment::Index(j), key(choice)]);
+                            let choice_parent = Parent { path: &path, choice: Some(choice), at: choice_at };
+                            walk_project(&choice_spec.children, &choice_spec.childoptions, files, &mut node, &choice_parent);
+                        }
+                    }
                 }
             }
-        } else if let Some(leaf) = child.as_str() {
-            if let Some(opts) = &child_options {
-                let node_type = get_node_type(&Mapping::new(), leaf, &Some(opts.clone()));
-                parent.append(node_type);
-            } else {
-                parent.append(LeafNodeType::Text { name: leaf.to_string() });
+            Child::Leaf(leaf) => {
+                let node_type = get_node_type(&NodeSpec::default(), leaf, child_options, with_choice(None, parent));
+                parent_node.append(node_type);
             }
         }
     }
 }
 
-fn get_options(options: &Sequence) -> Vec<String> {
-    options.iter().map(|o|
-        o.as_str().expect(format!("could not read option: {:?}", o).as_str()).to_string()
-    )
-        .collect()
+fn key(name: &str) -> Segment {
+    Segment::Key(name.to_string())
 }
 
-fn get_node_type(project: &Mapping, name: &str, child_options: &Option<Vec<String>>) -> LeafNodeType {
-    let options = project.get("options");
-    let seq_options = options
-        .map(|o| o.as_sequence())
-        .flatten()
-        .map(|s| get_options(s));
+fn yaml_path(path: &[Segment], segments: &[Segment]) -> YamlPath {
+    path.iter().chain(segments).cloned().collect()
+}
+
+fn child_path(parent_path: &str, name: &str) -> String {
+    if parent_path.is_empty() { name.to_string() } else { format!("{}/{}", parent_path, name) }
+}
+
+fn absolute(path: &Path) -> std::path::PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn condition(when: &Option<When>) -> Option<Condition> {
+    when.as_ref().map(|when| Condition(when.0.clone()))
+}
+
+fn rules(spec: Option<&RulesSpec>) -> Rules {
+    let Some(spec) = spec else { return Rules::default() };
+    Rules {
+        required: spec.required,
+        pattern: spec.pattern.clone(),
+        min_length: spec.min_length,
+        max_length: spec.max_length,
+        charset: spec.charset.clone(),
+        path_component: spec.path_component,
+    }
+}
+
+fn transforms(spec: &Option<Transforms>) -> Vec<Transform> {
+    spec.as_ref().map(|transforms| transforms.0.clone()).unwrap_or_default()
+}
+
+/// Adds the choice of the parent option to the condition of a node in a per-option subtree.
+fn with_choice(when: Option<Condition>, parent: &Parent) -> Option<Condition> {
+    let Some(choice) = parent.choice else { return when };
+    let mut when = when.unwrap_or_default();
+    when.0.insert(0, (parent.path.to_string(), vec![choice.to_string()]));
+    Some(when)
+}
+
+fn get_node_type(spec: &NodeSpec, name: &str, child_options: &Option<Vec<String>>, when: Option<Condition>) -> LeafNodeType {
+    let seq_options = match &spec.options {
+        Some(Options::List(list)) => Some(list.iter().map(|item| item.name().to_string()).collect::<Vec<String>>()),
+        _ => None,
+    };
 
     // concat child_options to options or use child_options if options is None
     let all_options = match (seq_options, child_options) {
         (None, None) => { None }
         (Some(opt_list), None) => { Some(opt_list) }
-        (None, Some(child_opts)) => { Some(child_opts.iter().map(|s| s.clone()).collect()) }
-        (Some(opt_list), Some(child_opts)) => { Some(child_opts.iter().map(|s| s.clone()).chain(opt_list).collect()) }
+        (None, Some(child_opts)) => { Some(child_opts.to_vec()) }
+        (Some(opt_list), Some(child_opts)) => { Some(child_opts.iter().cloned().chain(opt_list).collect()) }
     };
 
 
-    return if let Some(opt_list) = all_options {
+    if let Some(opt_list) = all_options {
         LeafNodeType::Option {
-            options: StatefulList::with_items(opt_list.iter().map(|i| (i.clone(), false)).collect(), false),
+            options: StatefulList::with_items(opt_list.iter().map(|i| (i.clone(), false)).collect(), spec.multiselect),
             name: name.to_string(),
+            when,
         }
-    } else if let Some(opt_str) = options.map(|o| o.as_str()).flatten() {
-        LeafNodeType::TextInput { name: name.to_string(), input: opt_str.to_string() }
+    } else if let Some(Options::Input(opt_str)) = &spec.options {
+        LeafNodeType::TextInput { name: name.to_string(), input: opt_str.to_string(), rules: rules(spec.rules.as_deref()), transform: transforms(&spec.transform), when }
     } else {
-        LeafNodeType::Text { name: name.to_string() }
-    };
+        LeafNodeType::Text { name: name.to_string(), when }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+    use super::*;
+
+    fn parse_file(path: &str) -> Result<Tree<LeafNodeType>, Diagnostics> {
+        parse_project_yaml(&fs::read_to_string(path).unwrap(), path, None).map(|(tree, _)| tree)
+    }
+
+    #[test]
+    fn test_parse_structure() {
+        let tree = parse_file("structure.yaml").unwrap();
+        let mut formatted = String::new();
+        tree.write_formatted(&mut formatted).unwrap();
+        assert!(formatted.contains("\"ProjectType\" with [Song, Beat, Podcast]"));
+        assert!(formatted.contains("\"ProjectTest\" with [eins, zwei, drei]"));
+        // `- test2:` has an empty body and is a plain folder
+        assert!(formatted.contains("test2"));
+    }
+
+    #[test]
+    fn test_parse_without_children() {
+        let tree = parse_file("test/no_children.yaml").unwrap();
+        assert!(tree.root().unwrap().first_child().is_none());
+    }
+
+    #[test]
+    fn test_child_options() {
+        let tree = parse_file("test/working.yaml").unwrap();
+        let mut formatted = String::new();
+        tree.write_formatted(&mut formatted).unwrap();
+        assert!(formatted.contains("\"Project\" with [Ableton, Fl Studio]"));
+        assert!(formatted.contains("\"Output\" with [Ableton, Fl Studio]"));
+    }
+
+    #[test]
+    fn test_file_nodes() {
+        let tree = parse_file("test/files.yaml").unwrap();
+        let files: Vec<(String, FileContent)> = tree.root().unwrap().traverse_pre_order()
+            .filter_map(|n| match n.data() {
+                LeafNodeType::File { name, content, .. } => Some((name.clone(), content.clone())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(files[0], (String::from("README.md"), FileContent::Inline(String::from("# New project\n"))));
+        assert!(matches!(&files[1].1, FileContent::Path(p) if p.is_absolute() && p.ends_with("test/files/gitignore")));
+        assert_eq!(files[2], (String::from(".keep"), FileContent::Empty));
+        assert_eq!(files[4], (String::from("latest"), FileContent::Symlink(PathBuf::from("{{ProjectName}}.als"))));
+        let modes: Vec<Option<u32>> = tree.root().unwrap().traverse_pre_order()
+            .filter_map(|n| match n.data() {
+                LeafNodeType::File { mode, .. } => Some(*mode),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(modes, vec![None, None, None, Some(0o755), None]);
+        let (_, settings) = parse_project_yaml(&fs::read_to_string("test/files.yaml").unwrap(), "test/files.yaml", None).unwrap();
+        assert_eq!(settings.hooks, vec![String::from("touch .hooked")]);
+    }
+
+    #[test]
+    fn test_includes() {
+        let tree = parse_file("test/include.yaml").unwrap();
+        let names: Vec<&str> = tree.root().unwrap().traverse_pre_order().map(|n| n.data().get_name()).collect();
+        assert_eq!(names, vec![
+            "Location", "ProjectName", "Master", "Stems", "Samples", "Drums", ".gitignore", "Clicks",
+            "Artwork", "Cover", "{{ProjectName}}_press",
+        ]);
+        // content files of included nodes are relative to the fragment
+        let gitignore = tree.root().unwrap().traverse_pre_order().find_map(|n| match n.data() {
+            LeafNodeType::File { content: FileContent::Path(p), .. } => Some(p.clone()),
+            _ => None,
+        });
+        assert!(gitignore.is_some_and(|p| p.is_file()));
+        let clicks = tree.root().unwrap().traverse_pre_order().find_map(|n| match n.data() {
+            LeafNodeType::File { content: FileContent::Copy { from, .. }, .. } => Some(from.clone()),
+            _ => None,
+        });
+        assert!(clicks.is_some_and(|p| p.is_dir()));
+    }
+
+    #[test]
+    fn test_extends() {
+        let tree = parse_file("test/extends/variant.yaml").unwrap();
+        let names: Vec<&str> = tree.root().unwrap().traverse_pre_order().map(|n| n.data().get_name()).collect();
+        assert_eq!(names, vec!["Location", "ProjectType", "ProjectName", "Stems", "Output", "Contracts"]);
+        let output = tree.root().unwrap().traverse_pre_order().find(|n| n.data().get_name() == "Output").unwrap();
+        match output.data() {
+            LeafNodeType::Option { options, .. } => assert_eq!(options.join_names_with(", "), "Ableton"),
+            other => panic!("expected an option, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_unknown_key() {
+        let err = parse_project_yaml("project:\n  default_location: ~/x\n  children:\n    - A:\n        chilren: [b]", "t.yaml", None).unwrap_err();
+        assert_eq!(err.0[0].message, "unknown key `chilren` in `A`");
+    }
+
+    #[test]
+    fn test_unsupported_version() {
+        assert!(parse_project_yaml("version: 2\nproject:\n  default_location: ~/x", "t.yaml", None).is_err());
+    }
 }
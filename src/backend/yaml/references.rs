@@ -0,0 +1,204 @@
+use crate::backend::tree::nodes::path_matches;
+use crate::backend::tree::substitute::placeholders;
+use crate::backend::tree::tokens::Token;
+use crate::backend::yaml::diagnostic::Diagnostics;
+use crate::backend::yaml::model::{Child, Options, Template, When};
+use crate::backend::yaml::source_map::{Segment, YamlPath};
+use crate::backend::yaml::validator::Report;
+
+/// A node of the template with the texts that can hold `{{Name}}` placeholders.
+struct Node {
+    path: String,
+    texts: Vec<(YamlPath, String)>,
+    /// The choices of an option node.
+    choices: Option<Vec<String>>,
+    /// The `when` entries, the node names with the answers they need.
+    conditions: Vec<(YamlPath, String, Vec<String>)>,
+}
+
+/// A placeholder pointing from one node to another.
+struct Reference {
+    target: usize,
+    at: YamlPath,
+}
+
+/// Checks that every placeholder and every `when` names a node of the template and that
+/// no node depends on itself through its placeholders. Placeholders that name no node have
+/// to be a built-in token like `{{date}}`.
+pub fn check_references(template: &Template, report: &Report) -> Diagnostics {
+    let mut nodes = Vec::new();
+    collect_nodes(template, &mut nodes);
+    let mut diagnostics = Diagnostics::default();
+    let error = |at: &[Segment], message: String, help: String| report.diagnostic(at, message, help);
+
+    let mut references: Vec<Vec<Reference>> = Vec::new();
+    for node in &nodes {
+        let mut node_references = Vec::new();
+        for (at, text) in &node.texts {
+            let Ok(found) = placeholders(text) else {
+                diagnostics.push(error(at, format!("`{}` has a `{{{{` without `}}}}`", text),
+                                       String::from("close the placeholder, for example `{{ProjectName}}`")));
+                continue;
+            };
+            let counters = found.iter().filter(|p| matches!(Token::parse(&p.key), Some(Ok(Token::Counter(_))))).count();
+            if counters > 1 {
+                diagnostics.push(error(at, format!("`{}` has {} counters, a name can only have one", text, counters),
+                                       String::from("the counter numbers the whole name, keep one `{{counter}}`")));
+            }
+            for placeholder in found {
+                match (nodes.iter().position(|n| path_matches(&n.path, &placeholder.key)), Token::parse(&placeholder.key)) {
+                    (Some(target), _) => node_references.push(Reference { target, at: at.clone() }),
+                    (None, Some(Ok(_))) => {}
+                    (None, Some(Err(message))) => diagnostics.push(error(at, message,
+                                                                         String::from("for example `{{date:%Y-%m-%d}}` or `{{counter:3}}`"))),
+                    (None, None) => diagnostics.push(error(at, format!("unknown reference `{{{{{}}}}}`", placeholder.key),
+                                                           String::from("placeholders name another node of the template or the end of its path, for example `{{ProjectName}}`, or a built-in token like `{{date}}`"))),
+                }
+            }
+        }
+        references.push(node_references);
+    }
+
+    for node in &nodes {
+        for (at, name, answers) in &node.conditions {
+            let Some(target) = nodes.iter().find(|n| path_matches(&n.path, name)) else {
+                diagnostics.push(error(at, format!("unknown node `{}` in `when`", name),
+                                       String::from("name a node of the template or the end of its path, for example `ProjectType`")));
+                continue;
+            };
+            if target.path == node.path || target.path.starts_with(&format!("{}/", node.path)) {
+                diagnostics.push(error(at, format!("the `when` of `{}` depends on its own branch", node.path),
+                                       String::from("a condition can only use answers from outside the branch it hides")));
+                continue;
+            }
+            let Some(choices) = &target.choices else { continue };
+            for answer in answers.iter().filter(|a| !choices.contains(a)) {
+                diagnostics.push(error(at, format!("`{}` is not an option of `{}`", answer, target.path),
+                                       format!("choose one of {}", choices.join(", "))));
+            }
+        }
+    }
+
+    for cycle in find_cycles(&references) {
+        let chain: Vec<&str> = cycle.iter().map(|(node, _)| nodes[*node].path.as_str()).collect();
+        let first = nodes[cycle[0].0].path.as_str();
+        diagnostics.push(error(&cycle[0].1, format!("circular reference {} -> {}", chain.join(" -> "), first),
+                               String::from("a name cannot depend on itself, remove one of the placeholders")));
+    }
+    diagnostics
+}
+
+/// Every cycle once, as the nodes on it with the position of the placeholder leaving each node.
+fn find_cycles(references: &[Vec<Reference>]) -> Vec<Vec<(usize, YamlPath)>> {
+    fn visit(node: usize, references: &[Vec<Reference>], state: &mut [u8], stack: &mut Vec<(usize, YamlPath)>, cycles: &mut Vec<Vec<(usize, YamlPath)>>) {
+        // 0 = not visited, 1 = on the current path, 2 = done
+        state[node] = 1;
+        for reference in &references[node] {
+            stack.push((node, reference.at.clone()));
+            match state[reference.target] {
+                0 => visit(reference.target, references, state, stack, cycles),
+                1 => {
+                    let start = stack.iter().position(|(n, _)| *n == reference.target).unwrap_or(0);
+                    cycles.push(stack[start..].to_vec());
+                }
+                _ => {}
+            }
+            stack.pop();
+        }
+        state[node] = 2;
+    }
+    let mut state = vec![0; references.len()];
+    let mut cycles = Vec::new();
+    for node in 0..references.len() {
+        if state[node] == 0 {
+            visit(node, references, &mut state, &mut Vec::new(), &mut cycles);
+        }
+    }
+    cycles
+}
+
+fn collect_nodes(template: &Template, nodes: &mut Vec<Node>) {
+    let project = &template.project;
+    let project_path = vec![key("project")];
+    let root = Node {
+        path: String::from("Location"),
+        texts: vec![(child(&project_path, key("default_location")), project.default_location.clone())],
+        choices: None,
+        conditions: Vec::new(),
+    };
+    nodes.push(root);
+    collect_children(project.children.as_deref(), project.childoptions.as_deref(), &project_path, "", nodes);
+}
+
+/// Collects the children of the node `parent_path`, whose body is at `yaml_path`.
+fn collect_children(children: Option<&[Child]>, child_options: Option<&[String]>, yaml_path: &[Segment], parent_path: &str, nodes: &mut Vec<Node>) {
+    let children_path = child(yaml_path, key("children"));
+    let child_options_path = child(yaml_path, key("childoptions"));
+    let child_options: Vec<(YamlPath, String)> = child_options.unwrap_or_default().iter().enumerate()
+        .map(|(i, choice)| (child(&child_options_path, Segment::Index(i)), choice.clone()))
+        .collect();
+
+    for (i, entry) in children.unwrap_or_default().iter().enumerate() {
+        let entry_path = child(&children_path, Segment::Index(i));
+        let (name, name_path, spec) = match entry {
+            Child::Leaf(name) => (name.as_str(), entry_path, None),
+            Child::Node { name, spec } => (name.as_str(), child(&entry_path, key(name)), Some(spec)),
+        };
+        let path = if parent_path.is_empty() { name.to_string() } else { format!("{}/{}", parent_path, name) };
+
+        // mirrors the node types of the parser: files and plain folders are named by
+        // the template, text inputs by their input and options by their choices
+        let options = spec.and_then(|s| s.options.as_ref());
+        let is_file = spec.is_some_and(|s| s.file.is_some() || s.symlink.is_some());
+        let options_path = child(&name_path, key("options"));
+        let mut texts = Vec::new();
+        match options {
+            _ if is_file => texts.push((name_path.clone(), name.to_string())),
+            Some(Options::List(items)) => {
+                texts.extend(child_options.iter().cloned());
+                texts.extend(items.iter().enumerate().map(|(j, item)| (child(&options_path, Segment::Index(j)), item.name().to_string())));
+            }
+            _ if !child_options.is_empty() => texts.extend(child_options.iter().cloned()),
+            Some(Options::Input(default)) => texts.push((options_path.clone(), default.clone())),
+            _ => texts.push((name_path.clone(), name.to_string())),
+        }
+        if let Some(target) = spec.and_then(|s| s.symlink.as_ref()) {
+            texts.push((child(&name_path, key("symlink")), target.clone()));
+        }
+        let choices = match options {
+            _ if is_file => None,
+            Some(Options::List(_)) => Some(texts.iter().map(|(_, choice)| choice.clone()).collect()),
+            _ if !child_options.is_empty() => Some(texts.iter().map(|(_, choice)| choice.clone()).collect()),
+            _ => None,
+        };
+        let conditions = spec.and_then(|s| s.when.as_ref()).map(|when| conditions(when, &child(&name_path, key("when")))).unwrap_or_default();
+        nodes.push(Node { path: path.clone(), texts, choices, conditions });
+        if let Some(spec) = spec {
+            collect_children(spec.children.as_deref(), spec.childoptions.as_deref(), &name_path, &path, nodes);
+        }
+        // per-option subtrees hang below the option node as well
+        if let Some(Options::List(items)) = options {
+            for (j, item) in items.iter().enumerate() {
+                let Child::Node { name: choice, spec: choice_spec } = item else { continue };
+                let choice_path = child(&child(&options_path, Segment::Index(j)), key(choice));
+                collect_children(choice_spec.children.as_deref(), choice_spec.childoptions.as_deref(), &choice_path, &path, nodes);
+            }
+        }
+    }
+}
+
+fn conditions(when: &When, when_path: &[Segment]) -> Vec<(YamlPath, String, Vec<String>)> {
+    when.0.iter()
+        .map(|(name, answers)| (child(when_path, key(name)), name.clone(), answers.clone()))
+        .collect()
+}
+
+fn key(name: &str) -> Segment {
+    Segment::Key(name.to_string())
+}
+
+fn child(path: &[Segment], segment: Segment) -> YamlPath {
+    let mut path = path.to_vec();
+    path.push(segment);
+    path
+}
@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+
+use yaml_rust2::parser::{Event, Parser};
+use yaml_rust2::scanner::{Marker, TScalarStyle};
+
+/// One step of the path from the document root to a yaml node.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+pub type YamlPath = Vec<Segment>;
+
+/// A position in the template source, `line` and `column` start at 1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+    pub len: usize,
+}
+
+/// Remembers where the keys and list items of a yaml document are.
+///
+/// serde_yaml does not keep positions in its `Value`, so the source is run through the
+/// yaml-rust2 event parser a second time. Keys point at the key, list items at the item,
+/// or at the first key when the item is a mapping.
+pub struct SourceMap<'a> {
+    lines: Vec<&'a str>,
+    spans: HashMap<YamlPath, Span>,
+    /// The line the text of each scalar value starts on.
+    text_lines: HashMap<YamlPath, usize>,
+}
+
+enum Frame {
+    Mapping { path: YamlPath, key: Option<Segment>, item: Option<YamlPath> },
+    Sequence { path: YamlPath, next_index: usize },
+}
+
+impl<'a> SourceMap<'a> {
+    pub fn new(source: &'a str) -> SourceMap<'a> {
+        let mut source_map = SourceMap { lines: source.lines().collect(), spans: HashMap::new(), text_lines: HashMap::new() };
+        let mut parser = Parser::new_from_str(source);
+        let mut stack: Vec<Frame> = vec![];
+        // a document that does not parse keeps the positions up to the error
+        while let Ok((event, marker)) = parser.next_token() {
+            match event {
+                Event::Scalar(value, style, ..) => {
+                    let len = match style {
+                        TScalarStyle::Plain => value.chars().count(),
+                        TScalarStyle::SingleQuoted | TScalarStyle::DoubleQuoted => value.chars().count() + 2,
+                        // block scalars start on the next line, only its first character is marked
+                        TScalarStyle::Literal | TScalarStyle::Folded => 1,
+                    };
+                    let span = source_map.span_at(marker, len);
+                    if let Some(Frame::Mapping { path, key: key @ None, item }) = stack.last_mut() {
+                        let mut key_path = path.clone();
+                        key_path.push(Segment::Key(value));
+                        source_map.spans.insert(key_path.clone(), span);
+                        if let Some(item) = item.take() {
+                            source_map.spans.insert(item, span);
+                        }
+                        *key = key_path.pop();
+                    } else {
+                        let path = node_path(&mut stack);
+                        // block scalars are marked at their first line that is not empty
+                        let line = match style {
+                            TScalarStyle::Literal | TScalarStyle::Folded => marker.line() - value.chars().take_while(|c| *c == '\n').count(),
+                            _ => marker.line(),
+                        };
+                        source_map.text_lines.insert(path.clone(), line);
+                        source_map.spans.entry(path).or_insert(span);
+                    }
+                }
+                Event::Alias(_) => {
+                    let path = node_path(&mut stack);
+                    let span = source_map.span_at(marker, 1);
+                    source_map.spans.entry(path).or_insert(span);
+                }
+                Event::MappingStart(..) => {
+                    let path = node_path(&mut stack);
+                    let item = matches!(path.last(), Some(Segment::Index(_))).then(|| path.clone());
+                    stack.push(Frame::Mapping { path, key: None, item });
+                }
+                Event::SequenceStart(..) => {
+                    let path = node_path(&mut stack);
+                    if matches!(path.last(), Some(Segment::Index(_))) {
+                        let span = source_map.span_at(marker, 1);
+                        source_map.spans.insert(path.clone(), span);
+                    }
+                    stack.push(Frame::Sequence { path, next_index: 0 });
+                }
+                Event::MappingEnd | Event::SequenceEnd => {
+                    stack.pop();
+                }
+                Event::StreamEnd => break,
+                _ => {}
+            }
+        }
+        source_map
+    }
+
+    /// The span of the node at `path`, or of its closest indexed parent.
+    pub fn span(&self, path: &[Segment]) -> Option<Span> {
+        (0..=path.len()).rev().find_map(|len| self.spans.get(&path[..len]).copied())
+    }
+
+    /// The line the first line of the text at `path` is on, block scalars start below their key.
+    pub fn text_line(&self, path: &[Segment]) -> Option<usize> {
+        self.text_lines.get(path).copied()
+    }
+
+    pub fn line(&self, line: usize) -> Option<&'a str> {
+        line.checked_sub(1).and_then(|i| self.lines.get(i)).copied()
+    }
+
+    // the parser counts characters from 0, spans count bytes from 1
+    fn span_at(&self, marker: Marker, len: usize) -> Span {
+        let text = self.line(marker.line()).unwrap_or("");
+        let column = text.char_indices().nth(marker.col()).map(|(i, _)| i).unwrap_or(text.len());
+        Span { line: marker.line(), column: column + 1, len }
+    }
+}
+
+/// The path of the node that starts next, moving its parent on to the following entry.
+///
+/// Collections and aliases used as mapping keys are filed under an empty key.
+fn node_path(stack: &mut [Frame]) -> YamlPath {
+    match stack.last_mut() {
+        None => vec![],
+        Some(Frame::Mapping { path, key, .. }) => {
+            let segment = key.take().unwrap_or_else(|| {
+                // the value of a collection key is filed under the same empty key
+                *key = Some(Segment::Key(String::new()));
+                Segment::Key(String::new())
+            });
+            let mut path = path.clone();
+            path.push(segment);
+            path
+        }
+        Some(Frame::Sequence { path, next_index }) => {
+            let mut path = path.clone();
+            path.push(Segment::Index(*next_index));
+            *next_index += 1;
+            path
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(k: &str) -> Segment {
+        Segment::Key(k.to_string())
+    }
+
+    #[test]
+    fn test_block_positions() {
+        let source = "project:\n  default_location: \"~/music\" # home\n  children:\n    - ProjectType:\n        options:\n          - Song\n    - Stems\n";
+        let map = SourceMap::new(source);
+        assert_eq!(map.span(&[key("project"), key("default_location")]), Some(Span { line: 2, column: 3, len: 16 }));
+        let project_type = vec![key("project"), key("children"), Segment::Index(0), key("ProjectType")];
+        assert_eq!(map.span(&project_type), Some(Span { line: 4, column: 7, len: 11 }));
+        let mut song = project_type.clone();
+        song.extend([key("options"), Segment::Index(0)]);
+        assert_eq!(map.span(&song), Some(Span { line: 6, column: 13, len: 4 }));
+        assert_eq!(map.span(&[key("project"), key("children"), Segment::Index(1)]), Some(Span { line: 7, column: 7, len: 5 }));
+    }
+
+    #[test]
+    fn test_same_indent_sequence_and_fallback() {
+        let source = "children:\n- a\n- b:\n    children: [c]\nnext: 1\n";
+        let map = SourceMap::new(source);
+        assert_eq!(map.span(&[key("children"), Segment::Index(1)]).unwrap().line, 3);
+        assert_eq!(map.span(&[key("next")]).unwrap().line, 5);
+        // nodes without a position of their own use the closest parent
+        let missing = [key("children"), Segment::Index(1), key("b"), key("options"), Segment::Index(0)];
+        assert_eq!(map.span(&missing).unwrap(), Span { line: 3, column: 3, len: 1 });
+    }
+
+    #[test]
+    fn test_flow_positions() {
+        let source = "children:\n  - b: {children: [c, 'd e'], options: [x]}\n  - [f]\n";
+        let map = SourceMap::new(source);
+        let b = vec![key("children"), Segment::Index(0), key("b")];
+        let mut children = b.clone();
+        children.push(key("children"));
+        assert_eq!(map.span(&children), Some(Span { line: 2, column: 9, len: 8 }));
+        children.push(Segment::Index(1));
+        assert_eq!(map.span(&children), Some(Span { line: 2, column: 23, len: 5 }));
+        let mut options = b.clone();
+        options.extend([key("options"), Segment::Index(0)]);
+        assert_eq!(map.span(&options), Some(Span { line: 2, column: 41, len: 1 }));
+        assert_eq!(map.span(&[key("children"), Segment::Index(1)]), Some(Span { line: 3, column: 5, len: 1 }));
+    }
+
+    #[test]
+    fn test_block_scalars_are_not_keys() {
+        let source = "file:\n  content: |\n    name: {{ name }}\n    - item\n  path: x\n";
+        let map = SourceMap::new(source);
+        assert_eq!(map.span(&[key("file"), key("content")]), Some(Span { line: 2, column: 3, len: 7 }));
+        assert_eq!(map.span(&[key("file"), key("path")]), Some(Span { line: 5, column: 3, len: 4 }));
+        // `name:` inside the text is not indexed, the lookup falls back to `content`
+        assert_eq!(map.span(&[key("file"), key("content"), key("name")]).unwrap().line, 2);
+        assert_eq!(map.span(&[key("name")]), None);
+    }
+
+    #[test]
+    fn test_text_lines() {
+        let source = "a: |\n  one\n  two\nb: >-\n\n  three\nc: \"four\"\nd:\n  five\n";
+        let map = SourceMap::new(source);
+        assert_eq!(map.text_line(&[key("a")]), Some(2));
+        // the empty first line is part of the text
+        assert_eq!(map.text_line(&[key("b")]), Some(5));
+        assert_eq!(map.text_line(&[key("c")]), Some(7));
+        assert_eq!(map.text_line(&[key("d")]), Some(9));
+    }
+}
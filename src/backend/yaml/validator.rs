@@ -0,0 +1,905 @@
+use serde_yaml::Value;
+use crate::backend::yaml::diagnostic::{Diagnostic, Diagnostics};
+use std::path::{Path, PathBuf};
+use crate::backend::yaml::include::{expand, value_at, Sources};
+use crate::backend::yaml::model::{Child, CopySpec, FileSpec, NodeSpec, Options, Project, RulesSpec, Template, CURRENT_VERSION};
+use crate::backend::tree::render::check_syntax;
+use crate::backend::tree::rules::anchored;
+use crate::backend::yaml::references::check_references;
+use crate::backend::yaml::source_map::{Segment, SourceMap, Span, YamlPath};
+
+/// Parses and validates a template, collecting every problem instead of stopping at the first.
+///
+/// `name` picks a template below the `templates` of the file, without it the file is the template.
+/// The files the template was put together from come with it, to point back into them.
+pub fn parse_template(source: &str, file: &str, name: Option<&str>) -> Result<(Template, Sources), Diagnostics> {
+    validate(source, file, name).map(|(_, sources, template)| (template, sources))
+}
+
+/// The template with its includes and the templates it extends merged in, after validating it.
+pub fn check_template(source: &str, file: &str, name: Option<&str>) -> Result<(Value, Sources), Diagnostics> {
+    validate(source, file, name).map(|(value, sources, _)| (value, sources))
+}
+
+/// Reads the template in two passes.
+///
+/// The shape of the yaml is checked by deserializing it into the [`Template`] model, the
+/// serde errors become the diagnostics. The node with a problem is cut out and the rest is
+/// deserialized again, so one run reports the problems of every node. The template that is
+/// left is then checked for what the model cannot express, like files that do not exist or
+/// placeholders naming no node.
+fn validate(source: &str, file: &str, name: Option<&str>) -> Result<(Value, Sources, Template), Diagnostics> {
+    let source_map = SourceMap::new(source);
+    let value: Value = serde_yaml::from_str(source)
+        .map_err(|e| serde_diagnostic(&e, file, &source_map))?;
+    let root = match name {
+        Some(name) => vec![key("templates"), key(name)],
+        None => Vec::new(),
+    };
+    if value_at(&value, &root).is_none() {
+        let span = source_map.span(&[key("templates")]);
+        return Err(Diagnostic::new(file, format!("there is no template named `{}`", name.unwrap_or_default()), span, &source_map)
+            .with_help("the names of the templates are the keys below `templates:`").into());
+    }
+    let (value, sources, mut diagnostics) = expand(value, source, file, &root);
+    if value.get("templates").is_some() {
+        diagnostics.push(sources.diagnostic(&[key("templates")], "this file holds several templates",
+                                            "choose one of them by name, for example with `--template-name`"));
+        return Err(diagnostics);
+    }
+
+    let mut report = Report { sources: &sources, removed: Vec::new() };
+    let mut pruned = value.clone();
+    let mut reported: Vec<YamlPath> = Vec::new();
+    let template = loop {
+        let error = match serde_path_to_error::deserialize::<_, Template>(&pruned) {
+            Ok(template) => break Some(template),
+            Err(error) => error,
+        };
+        let at = yaml_path(error.path());
+        let original = report.original(&at);
+        // cutting out a node can make its parent fail, that is the same problem again
+        if !reported.iter().any(|path| path.starts_with(&original)) {
+            diagnostics.push(shape_diagnostic(error.inner(), &at, &report));
+            reported.push(original);
+        }
+        if !report.prune(&mut pruned, &at) {
+            break None;
+        }
+    };
+
+    let Some(template) = template else { return Err(diagnostics) };
+    let mut checker = Checker { base_dir: template_dir(file), report: &report, diagnostics };
+    checker.template(&template);
+    let mut diagnostics = checker.diagnostics;
+    for diagnostic in check_references(&template, &report).0 {
+        diagnostics.push(diagnostic);
+    }
+    if !diagnostics.is_empty() {
+        return Err(diagnostics);
+    }
+    Ok((value, sources, template))
+}
+
+pub(super) fn serde_diagnostic(error: &serde_yaml::Error, file: &str, source_map: &SourceMap) -> Diagnostic {
+    let span = error.location().map(|l| Span { line: l.line(), column: l.column(), len: 1 });
+    let message = error.to_string();
+    let message = match message.rsplit_once(" at line ") {
+        Some((message, _)) => message.to_string(),
+        None => message,
+    };
+    Diagnostic::new(file, message, span, source_map)
+        .with_help("check the yaml syntax around this position, indentation has to use spaces and line up with its siblings")
+}
+
+/// The directory paths in a template are relative to.
+pub fn template_dir(file: &str) -> PathBuf {
+    Path::new(file).parent().map(Path::to_path_buf).unwrap_or_default()
+}
+
+/// Points the diagnostics for the pruned template at the yaml they were read from.
+pub(super) struct Report<'a> {
+    sources: &'a Sources,
+    /// The list entries cut out of the template, as the path of the list and the index of the entry.
+    removed: Vec<(YamlPath, usize)>,
+}
+
+impl Report<'_> {
+    pub(super) fn diagnostic(&self, at: &[Segment], message: impl Into<String>, help: impl Into<String>) -> Diagnostic {
+        self.sources.diagnostic(&self.original(at), message, help)
+    }
+
+    fn error(&self, at: &[Segment], message: impl Into<String>) -> Diagnostic {
+        self.sources.error(&self.original(at), message)
+    }
+
+    /// The path in the expanded template of the pruned node at `at`.
+    fn original(&self, at: &[Segment]) -> YamlPath {
+        let mut path = Vec::new();
+        for segment in at {
+            let segment = match segment {
+                Segment::Index(index) => {
+                    let mut removed: Vec<usize> = self.removed.iter()
+                        .filter(|(list, _)| *list == path)
+                        .map(|(_, removed)| *removed)
+                        .collect();
+                    removed.sort_unstable();
+                    let mut index = *index;
+                    for removed in removed {
+                        if removed <= index {
+                            index += 1;
+                        }
+                    }
+                    Segment::Index(index)
+                }
+                key => key.clone(),
+            };
+            path.push(segment);
+        }
+        path
+    }
+
+    /// Cuts the node at `at` out of `value`, false if there is nothing left to cut.
+    fn prune(&mut self, value: &mut Value, at: &[Segment]) -> bool {
+        let Some((last, parent)) = at.split_last() else { return false };
+        let Some(container) = value_at_mut(value, parent) else { return false };
+        match (last, container) {
+            (Segment::Key(name), Value::Mapping(mapping)) => mapping.remove(name.as_str()).is_some(),
+            (Segment::Index(index), Value::Sequence(items)) if *index < items.len() => {
+                let mut original = self.original(at);
+                if let Some(Segment::Index(removed)) = original.pop() {
+                    self.removed.push((original, removed));
+                }
+                items.remove(*index);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+fn value_at_mut<'v>(value: &'v mut Value, path: &[Segment]) -> Option<&'v mut Value> {
+    path.iter().try_fold(value, |value, segment| match segment {
+        Segment::Key(name) => value.get_mut(name.as_str()),
+        Segment::Index(index) => value.get_mut(*index),
+    })
+}
+
+fn yaml_path(path: &serde_path_to_error::Path) -> YamlPath {
+    use serde_path_to_error::Segment as Serde;
+    path.iter()
+        .map_while(|segment| match segment {
+            Serde::Seq { index } => Some(Segment::Index(*index)),
+            Serde::Map { key } | Serde::Enum { variant: key } => Some(Segment::Key(key.clone())),
+            Serde::Unknown => None,
+        })
+        .collect()
+}
+
+/// A diagnostic for a value that does not fit the model, from the serde error.
+fn shape_diagnostic(error: &serde_yaml::Error, at: &[Segment], report: &Report) -> Diagnostic {
+    let message = error.to_string();
+    // serde puts the names it talks about in backticks
+    let names: Vec<&str> = message.split('`').skip(1).step_by(2).collect();
+    match names.split_first() {
+        Some((name, expected)) if message.starts_with("unknown field") => {
+            let parent = &at[..at.len().saturating_sub(1)];
+            report.diagnostic(at, format!("unknown key `{}` in {}", name, describe(parent)), suggest(name, expected, "allowed keys here are"))
+        }
+        Some((name, expected)) if message.starts_with("unknown variant") => {
+            report.diagnostic(at, format!("unknown value `{}` for {}", name, describe(at)), suggest(name, expected, "use one of"))
+        }
+        Some((field, _)) if message.starts_with("missing field") => {
+            report.diagnostic(at, format!("{} has no `{}`", describe(at), field), format!("add `{}:` to {}", field, describe(at)))
+        }
+        _ => report.error(at, message),
+    }
+}
+
+fn suggest(name: &str, expected: &[&str], listing: &str) -> String {
+    match closest(name, expected) {
+        Some(suggestion) => format!("did you mean `{}`?", suggestion),
+        None => format!("{} {}", listing, expected.iter().map(|e| format!("`{}`", e)).collect::<Vec<_>>().join(", ")),
+    }
+}
+
+/// The checks of a template that has the right shape.
+struct Checker<'a> {
+    base_dir: PathBuf,
+    report: &'a Report<'a>,
+    diagnostics: Diagnostics,
+}
+
+impl Checker<'_> {
+    fn error(&mut self, path: &[Segment], message: impl Into<String>, help: impl Into<String>) {
+        let diagnostic = self.report.diagnostic(path, message, help);
+        self.diagnostics.push(diagnostic);
+    }
+
+    fn template(&mut self, template: &Template) {
+        if template.version == 0 || template.version > CURRENT_VERSION {
+            self.error(&[key("version")], format!("template version {} is not supported", template.version),
+                       format!("this scaffolder understands version {}", CURRENT_VERSION));
+        }
+        self.project(&template.project, &[key("project")]);
+    }
+
+    fn project(&mut self, project: &Project, path: &[Segment]) {
+        for (i, hook) in project.hooks.iter().enumerate() {
+            if hook.trim().is_empty() {
+                self.error(&child_path(&child_path(path, key("hooks")), Segment::Index(i)), "hook commands cannot be empty",
+                           "write the shell command to run in the project folder, or remove the entry");
+            }
+        }
+        self.children(project.children.as_deref(), project.childoptions.as_deref(), path);
+    }
+
+    fn children(&mut self, children: Option<&[Child]>, child_options: Option<&[String]>, path: &[Segment]) {
+        for (i, child) in children.unwrap_or_default().iter().enumerate() {
+            let entry_path = child_path(&child_path(path, key("children")), Segment::Index(i));
+            if child.name().trim().is_empty() {
+                self.error(&entry_path, "folder names cannot be empty", "give the folder a name or remove the entry");
+            }
+            if let Child::Node { name, spec } = child {
+                // childoptions turn every child into an option
+                self.node(spec, &child_path(&entry_path, key(name)), child_options.is_some());
+            }
+        }
+    }
+
+    fn node(&mut self, spec: &NodeSpec, path: &[Segment], has_child_options: bool) {
+        let is_input = matches!(spec.options, Some(Options::Input(_)));
+        if spec.multiselect && !has_child_options && !matches!(spec.options, Some(Options::List(_))) {
+            self.error(&child_path(path, key("multiselect")), "`multiselect` needs choices to check",
+                       "add a list of `options`, or remove `multiselect`");
+        }
+        if let Some(file) = &spec.file {
+            self.file_node(file, spec, path);
+        }
+        if let Some(copy) = &spec.copy {
+            self.copy_node(copy, spec, path);
+        }
+        if let Some(target) = &spec.symlink {
+            self.link_node(target, spec, path);
+        }
+        if spec.when.as_ref().is_some_and(|when| when.0.is_empty()) {
+            self.error(&child_path(path, key("when")), "`when` has to map node names to answers",
+                       "write the condition below `when:`, for example `ProjectType: Song`");
+        }
+        for (name, answers) in spec.when.iter().flat_map(|when| &when.0) {
+            if answers.is_empty() {
+                self.error(&child_path(&child_path(path, key("when")), key(name)), format!("`{}` in `when` needs an answer or a list of answers", name),
+                           format!("write `{}: Song` or `{}: [Song, Beat]`", name, name));
+            }
+        }
+        if let Some(rules) = &spec.rules {
+            let rules_path = child_path(path, key("rules"));
+            if is_input {
+                self.rules(rules, &rules_path);
+            } else {
+                self.error(&rules_path, "`rules` only apply to text inputs",
+                           "add `options: \"\"` to ask for a text, or remove `rules`");
+            }
+        }
+        if spec.transform.is_some() {
+            let transform_path = child_path(path, key("transform"));
+            if !is_input {
+                self.error(&transform_path, "`transform` only applies to text inputs",
+                           "add `options: \"\"` to ask for a text, or remove `transform`");
+            }
+        }
+        if let Some(Options::List(choices)) = &spec.options {
+            let options_path = child_path(path, key("options"));
+            for (i, choice) in choices.iter().enumerate() {
+                if let Child::Node { name, spec } = choice {
+                    self.choice(spec, &child_path(&child_path(&options_path, Segment::Index(i)), key(name)));
+                }
+            }
+        }
+        self.children(spec.children.as_deref(), spec.childoptions.as_deref(), path);
+    }
+
+    /// A choice with its own subtree, it can only have children.
+    fn choice(&mut self, spec: &NodeSpec, path: &[Segment]) {
+        for field in settings(spec).into_iter().filter(|field| !["children", "childoptions"].contains(field)) {
+            self.error(&child_path(path, key(field)), format!("a choice cannot have `{}`", field),
+                       "a choice only has `children` and `childoptions`, put the other settings on a child");
+        }
+        self.children(spec.children.as_deref(), spec.childoptions.as_deref(), path);
+    }
+
+    fn file_node(&mut self, file: &FileSpec, spec: &NodeSpec, path: &[Segment]) {
+        let file_path = child_path(path, key("file"));
+        for field in settings(spec).into_iter().filter(|field| ["children", "childoptions", "options"].contains(field)) {
+            self.error(&child_path(path, key(field)), format!("a file cannot have `{}`", field),
+                       "remove the key, or remove `file:` to make this node a folder");
+        }
+        if file.content.is_some() && file.path.is_some() {
+            self.error(&file_path, "a file can either have `content` or a `path`, not both",
+                       "keep the inline `content` or move it into the file at `path`");
+        }
+        if let Some(content_path) = &file.path {
+            if !self.base_dir.join(content_path).is_file() {
+                self.error(&child_path(&file_path, key("path")), format!("the content file `{}` does not exist", content_path),
+                           format!("paths are relative to the template directory `{}`", self.base_dir.display()));
+            }
+        }
+        if file.render {
+            self.file_template(file, &file_path);
+        }
+    }
+
+    /// Syntax errors of a file template, the variables are checked when it is rendered.
+    fn file_template(&mut self, file: &FileSpec, file_path: &[Segment]) {
+        const HELP: &str = "`{{ Name }}` inserts an answer, `{% if %}` and `{% for %}` blocks end with `{% endif %}` and `{% endfor %}`";
+        if let Some(content) = &file.content {
+            if let Err(e) = check_syntax(content) {
+                self.error(&child_path(file_path, key("content")), format!("the content is not a valid template, {}", e), HELP);
+            }
+        }
+        if let Some(path) = &file.path {
+            // a missing file is reported already
+            let Ok(source) = std::fs::read_to_string(self.base_dir.join(path)) else { return };
+            if let Err(e) = check_syntax(&source) {
+                self.error(&child_path(file_path, key("path")), format!("`{}` is not a valid template, {}", path, e), HELP);
+            }
+        }
+    }
+
+    fn copy_node(&mut self, copy: &CopySpec, spec: &NodeSpec, path: &[Segment]) {
+        let copy_path = child_path(path, key("copy"));
+        for field in settings(spec).into_iter().filter(|field| ["file", "children", "childoptions", "options", "multiselect"].contains(field)) {
+            self.error(&child_path(path, key(field)), format!("a copied node cannot have `{}`", field),
+                       "remove the key, or remove `copy:`");
+        }
+        let from_path = match copy {
+            CopySpec::From(_) => copy_path,
+            CopySpec::Full(_) => child_path(&copy_path, key("from")),
+        };
+        if !self.base_dir.join(copy.from()).exists() {
+            self.error(&from_path, format!("the asset `{}` does not exist", copy.from()),
+                       format!("paths are relative to the template directory `{}`", self.base_dir.display()));
+        }
+    }
+
+    fn link_node(&mut self, target: &str, spec: &NodeSpec, path: &[Segment]) {
+        for field in settings(spec).into_iter().filter(|field| ["file", "copy", "children", "childoptions", "options", "multiselect"].contains(field)) {
+            self.error(&child_path(path, key(field)), format!("a link cannot have `{}`", field),
+                       "remove the key, or remove `symlink:`");
+        }
+        if target.trim().is_empty() {
+            self.error(&child_path(path, key("symlink")), "`symlink` needs the path the link points at",
+                       "write the target, relative to the folder of the link, for example `symlink: ../shared`");
+        }
+    }
+
+    fn rules(&mut self, rules: &RulesSpec, path: &[Segment]) {
+        if let (Some(min), Some(max)) = (rules.min_length, rules.max_length) {
+            if min > max {
+                self.error(&child_path(path, key("min_length")), format!("`min_length` {} is more than `max_length` {}", min, max),
+                           "no answer can pass both, lower `min_length` or raise `max_length`");
+            }
+        }
+        if let Some(pattern) = &rules.pattern {
+            if let Err(err) = anchored(pattern) {
+                self.error(&child_path(path, key("pattern")), "`pattern` is not a valid regular expression",
+                           err.to_string().lines().last().unwrap_or_default().trim().to_string());
+            }
+        }
+        if rules.charset.as_ref().is_some_and(String::is_empty) {
+            self.error(&child_path(path, key("charset")), "`charset` has to list the allowed characters",
+                       "write them in quotes, ranges included, for example `charset: \"a-zA-Z0-9 _-\"`");
+        }
+    }
+}
+
+/// The keys that are set in the body of a node.
+fn settings(spec: &NodeSpec) -> Vec<&'static str> {
+    [
+        ("options", spec.options.is_some()),
+        ("children", spec.children.is_some()),
+        ("childoptions", spec.childoptions.is_some()),
+        ("multiselect", spec.multiselect),
+        ("when", spec.when.is_some()),
+        ("file", spec.file.is_some()),
+        ("rules", spec.rules.is_some()),
+        ("transform", spec.transform.is_some()),
+        ("copy", spec.copy.is_some()),
+        ("symlink", spec.symlink.is_some()),
+    ]
+    .into_iter()
+    .filter_map(|(field, set)| set.then_some(field))
+    .collect()
+}
+
+fn key(name: &str) -> Segment {
+    Segment::Key(name.to_string())
+}
+
+fn child_path(path: &[Segment], segment: Segment) -> YamlPath {
+    let mut path = path.to_vec();
+    path.push(segment);
+    path
+}
+
+fn describe(path: &[Segment]) -> String {
+    match path.iter().rev().find_map(|s| if let Segment::Key(k) = s { Some(k) } else { None }) {
+        Some(name) => format!("`{}`", name),
+        None => String::from("the template"),
+    }
+}
+
+/// The allowed name closest to `name`, if it is close enough to be a typo.
+fn closest<'a>(name: &str, allowed: &[&'a str]) -> Option<&'a str> {
+    allowed.iter()
+        .map(|candidate| (edit_distance(name, candidate), *candidate))
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let current = row[j + 1];
+            row[j + 1] = (prev + usize::from(ca != *cb)).min(row[j] + 1).min(current + 1);
+            prev = current;
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reports_all_problems() {
+        let source = "\
+project:
+  default_location: ~/music
+  children:
+    - ProjectType:
+        optons:
+          - Song
+    - Output:
+        children:
+          - 42
+    - ProjectName:
+        options: []
+";
+        let diagnostics = parse_template(source, "template.yaml", None).unwrap_err();
+        assert_eq!(diagnostics.len(), 3, "{}", diagnostics);
+        let first = &diagnostics.0[0];
+        assert_eq!(first.message, "unknown key `optons` in `ProjectType`");
+        assert_eq!(first.span, Some(Span { line: 5, column: 9, len: 6 }));
+        assert_eq!(first.help.as_deref(), Some("did you mean `options`?"));
+        assert_eq!(diagnostics.0[1].span.unwrap().line, 9);
+        assert_eq!(diagnostics.0[2].span.unwrap().line, 11);
+
+        let rendered = first.to_string();
+        assert!(rendered.contains("--> template.yaml:5:9"), "{}", rendered);
+        assert!(rendered.contains("5 |         optons:"), "{}", rendered);
+    }
+
+    #[test]
+    fn test_syntax_error_position() {
+        let diagnostics = parse_template("project:\n  default_location: ~/x\n   children: [\n", "t.yaml", None).unwrap_err();
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics.0[0].span.is_some());
+    }
+
+    #[test]
+    fn test_invalid_files() {
+        let source = "\
+project:
+  default_location: ~/x
+  children:
+    - README.md:
+        file:
+          content: hi
+          path: readme.md
+        children: [a]
+    - LICENSE:
+        file:
+          path: nope/LICENSE
+";
+        let diagnostics = parse_template(source, "test/t.yaml", None).unwrap_err();
+        let messages: Vec<&str> = diagnostics.0.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(messages, vec![
+            "a file cannot have `children`",
+            "a file can either have `content` or a `path`, not both",
+            "the content file `readme.md` does not exist",
+            "the content file `nope/LICENSE` does not exist",
+        ]);
+    }
+
+    #[test]
+    fn test_invalid_references() {
+        let source = "\
+project:
+  default_location: ~/x/{{Artist}}
+  children:
+    - A:
+        options: \"{{B}}\"
+    - B:
+        options: [\"{{A}}_mix\", Mono]
+    - \"{{Nope}}_stems\"
+    - \"{{A\"
+";
+        let diagnostics = parse_template(source, "t.yaml", None).unwrap_err();
+        let messages: Vec<&str> = diagnostics.0.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(messages, vec![
+            "unknown reference `{{Artist}}`",
+            "unknown reference `{{Nope}}`",
+            "`{{A` has a `{{` without `}}`",
+            "circular reference A -> B -> A",
+        ]);
+        assert_eq!(diagnostics.0[3].span.unwrap().line, 5);
+    }
+
+    #[test]
+    fn test_invalid_tokens() {
+        let source = "\
+project:
+  default_location: ~/x/{{date}}
+  children:
+    - \"{{date:%Y-%Q}}_{{user}}\"
+    - \"Take_{{counter:three}}\"
+    - \"{{hostname:local}}\"
+    - \"Take_{{counter}}_{{counter:2}}\"
+";
+        let diagnostics = parse_template(source, "t.yaml", None).unwrap_err();
+        let messages: Vec<&str> = diagnostics.0.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(messages, vec![
+            "`%Y-%Q` is not a valid date format",
+            "the width of `{{counter:three}}` has to be a number above 0",
+            "`{{hostname}}` takes no argument",
+            "`Take_{{counter}}_{{counter:2}}` has 2 counters, a name can only have one",
+        ]);
+    }
+
+    #[test]
+    fn test_invalid_conditions() {
+        let source = "\
+project:
+  default_location: ~/x
+  children:
+    - ProjectType:
+        options: [Song, Beat]
+    - Stems:
+        when:
+          ProjectType: Sogn
+    - Mix:
+        when:
+          Master: Song
+    - Output:
+        when:
+          Output/Inner: a
+        children:
+          - Inner:
+              options: [a, b]
+    - Broken:
+        when: Song
+";
+        let diagnostics = parse_template(source, "t.yaml", None).unwrap_err();
+        let messages: Vec<&str> = diagnostics.0.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(messages, vec![
+            "invalid type: string \"Song\", expected a mapping from node names to answers",
+            "`Sogn` is not an option of `ProjectType`",
+            "unknown node `Master` in `when`",
+            "the `when` of `Output` depends on its own branch",
+        ]);
+        assert_eq!(diagnostics.0[1].span.unwrap().line, 8);
+    }
+
+    #[test]
+    fn test_invalid_choices() {
+        let source = "\
+project:
+  default_location: ~/x
+  children:
+    - ProjectType:
+        options:
+          - Song
+          - Podcast:
+              chidren: [Episodes]
+          - Beat: Loops
+        childoptions:
+          - Shared:
+              children: [a]
+";
+        let diagnostics = parse_template(source, "t.yaml", None).unwrap_err();
+        let messages: Vec<&str> = diagnostics.0.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(messages, vec![
+            "unknown key `chidren` in `Podcast`",
+            "invalid type: string \"Loops\", expected the settings of a node, like `options` or `children`",
+            "invalid type: map, expected a string",
+        ]);
+        assert_eq!(diagnostics.0[0].help.as_deref(), Some("did you mean `children`?"));
+        assert_eq!(diagnostics.0[2].span.unwrap().line, 11);
+    }
+
+    #[test]
+    fn test_invalid_multiselect() {
+        let source = "\
+project:
+  default_location: ~/x
+  children:
+    - Name:
+        options: \"\"
+        multiselect: true
+    - DAW:
+        options: [Ableton]
+        multiselect: yes please
+";
+        let diagnostics = parse_template(source, "t.yaml", None).unwrap_err();
+        let messages: Vec<&str> = diagnostics.0.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(messages, vec!["invalid type: string \"yes please\", expected a boolean", "`multiselect` needs choices to check"]);
+        assert_eq!(diagnostics.0[0].span.unwrap().line, 9);
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        let source = "\
+project:
+  default_location: ~/x
+  children:
+    - DAW:
+        options: [Ableton]
+        rules:
+          required: true
+    - Name:
+        options: \"\"
+        rules:
+          required: yes please
+          min_length: 5
+          max_length: 2
+          pattern: \"[a-z\"
+          charst: a-z
+";
+        let diagnostics = parse_template(source, "t.yaml", None).unwrap_err();
+        let messages: Vec<&str> = diagnostics.0.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(messages, vec![
+            "invalid type: string \"yes please\", expected a boolean",
+            "unknown key `charst` in `rules`",
+            "`rules` only apply to text inputs",
+            "`min_length` 5 is more than `max_length` 2",
+            "`pattern` is not a valid regular expression",
+        ]);
+        assert_eq!(diagnostics.0[1].help.as_deref(), Some("did you mean `charset`?"));
+    }
+
+    #[test]
+    fn test_invalid_transforms() {
+        let source = "\
+project:
+  default_location: ~/x
+  children:
+    - Stems:
+        transform: slug
+    - Name:
+        options: \"\"
+        transform: [trim, snakecase, 3]
+    - Title:
+        options: \"\"
+        transform: camelCase
+";
+        let diagnostics = parse_template(source, "t.yaml", None).unwrap_err();
+        let messages: Vec<&str> = diagnostics.0.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(messages, vec![
+            "unknown value `snakecase` for `transform`",
+            "invalid type: integer `3`, expected a text",
+            "unknown value `camelCase` for `transform`",
+            "`transform` only applies to text inputs",
+        ]);
+        assert_eq!(diagnostics.0[0].help.as_deref(), Some("did you mean `snake_case`?"));
+        assert_eq!(diagnostics.0[0].span, Some(Span { line: 8, column: 27, len: 9 }));
+    }
+
+    #[test]
+    fn test_invalid_includes() {
+        let source = "\
+definitions:
+  A:
+    $ref: \"#/definitions/B\"
+  B:
+    - $ref: \"#/definitions/A\"
+project:
+  default_location: ~/x
+  children:
+    - $ref: \"#/definitions/Nope\"
+    - include: fragments/nope.yaml
+    - include: fragments/broken.yaml
+    - include: fragments/cycle.yaml
+    - $ref: \"#/definitions/A\"
+    - Stems:
+        optons: [a]
+";
+        let diagnostics = parse_template(source, "test/t.yaml", None).unwrap_err();
+        let messages: Vec<&str> = diagnostics.0.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(messages, vec![
+            "`test/t.yaml` has no fragment at `#/definitions/Nope`",
+            "the included file `fragments/nope.yaml` does not exist",
+            "include cycle test/fragments/cycle.yaml -> test/fragments/cycle.yaml",
+            "include cycle test/t.yaml#/definitions/A -> test/t.yaml#/definitions/B -> test/t.yaml#/definitions/A",
+            "unknown key `optons` in `Drums`",
+            "unknown key `optons` in `Stems`",
+        ]);
+        assert_eq!(diagnostics.0[0].span.unwrap().line, 9);
+        // problems inside an included file point into that file
+        assert_eq!(diagnostics.0[4].file, "test/fragments/broken.yaml");
+        assert_eq!(diagnostics.0[4].span.unwrap().line, 2);
+        assert_eq!(diagnostics.0[5].file, "test/t.yaml");
+        assert_eq!(diagnostics.0[5].span.unwrap().line, 15);
+    }
+
+    #[test]
+    fn test_invalid_extends() {
+        let source = "\
+extends: extends/broken_base.yaml
+project:
+  remove: [Nope]
+  children:
+    - Stems:
+        options: \"\"
+";
+        let diagnostics = parse_template(source, "test/t.yaml", None).unwrap_err();
+        let messages: Vec<&str> = diagnostics.0.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(messages, vec![
+            "the base template has no child `Nope` to remove here",
+            "unknown key `optons` in `Stems`",
+        ]);
+        assert_eq!(diagnostics.0[0].span.unwrap().line, 3);
+        assert_eq!(diagnostics.0[1].file, "test/extends/broken_base.yaml");
+        assert_eq!(diagnostics.0[1].span.unwrap().line, 5);
+
+        let diagnostics = parse_template("extends: extends/loop_a.yaml\n", "test/t.yaml", None).unwrap_err();
+        assert_eq!(diagnostics.0[0].message, "circular extends test/extends/loop_a.yaml -> test/extends/loop_b.yaml -> test/extends/loop_a.yaml");
+    }
+
+    #[test]
+    fn test_invalid_conflict_policy() {
+        let source = "\
+project:
+  default_location: ~/x
+  on_conflict: overwrite
+";
+        let diagnostics = parse_template(source, "t.yaml", None).unwrap_err();
+        assert_eq!(diagnostics.0[0].message, "unknown value `overwrite` for `on_conflict`");
+        assert_eq!(diagnostics.0[0].span.unwrap().line, 3);
+        assert_eq!(diagnostics.0[0].help.as_deref(), Some("use one of `abort`, `merge`, `suffix`, `prompt`"));
+    }
+
+    #[test]
+    fn test_invalid_copy() {
+        let source = "\
+project:
+  default_location: ~/x
+  children:
+    - Session.als:
+        copy: assets/missing.als
+    - Clicks:
+        copy: assets/clicks
+        children: [Extra]
+    - Artwork:
+        copy:
+          from: assets/artwork
+          render_names: yes please
+    - Notes:
+        copy:
+          render_names: true
+";
+        let diagnostics = parse_template(source, "test/t.yaml", None).unwrap_err();
+        let messages: Vec<&str> = diagnostics.0.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(messages, vec![
+            "invalid type: string \"yes please\", expected a boolean",
+            "`copy` has no `from`",
+            "the asset `assets/missing.als` does not exist",
+            "a copied node cannot have `children`",
+        ]);
+        assert_eq!(diagnostics.0[1].help.as_deref(), Some("add `from:` to `copy`"));
+        assert_eq!(diagnostics.0[2].help.as_deref(), Some("paths are relative to the template directory `test`"));
+    }
+
+    #[test]
+    fn test_invalid_links_modes_and_hooks() {
+        let source = "\
+project:
+  default_location: ~/x
+  hooks:
+    - git init
+    - \" \"
+  children:
+    - run.sh:
+        file:
+          mode: 789
+    - setup.sh:
+        file:
+          mode: \"0o750\"
+    - latest:
+        symlink: \"\"
+    - shared:
+        symlink: ../shared
+        children: [Extra]
+    - current:
+        symlink: \"{{Missing}}\"
+";
+        let diagnostics = parse_template(source, "t.yaml", None).unwrap_err();
+        let messages: Vec<&str> = diagnostics.0.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(messages, vec![
+            "`789` is not a file mode, write octal permissions like `755` or `644`",
+            "hook commands cannot be empty",
+            "`symlink` needs the path the link points at",
+            "a link cannot have `children`",
+            "unknown reference `{{Missing}}`",
+        ]);
+        assert_eq!(diagnostics.0[0].span.unwrap().line, 9);
+        assert_eq!(diagnostics.0[1].span.unwrap().line, 5);
+        assert_eq!(diagnostics.0[4].span.unwrap().line, 19);
+    }
+
+    #[test]
+    fn test_invalid_file_templates() {
+        let source = "\
+project:
+  default_location: ~/x
+  children:
+    - README.md:
+        file:
+          content: \"# {{ ProjectName }\\n{% if x %}\"
+          render: true
+    - notes.md:
+        file:
+          content: \"{{ not rendered\"
+    - .gitignore:
+        file:
+          path: files/gitignore
+          render: yes
+";
+        let diagnostics = parse_template(source, "test/t.yaml", None).unwrap_err();
+        let messages: Vec<&str> = diagnostics.0.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(messages.len(), 2, "{:?}", messages);
+        assert_eq!(messages[0], "invalid type: string \"yes\", expected a boolean");
+        assert!(messages[1].starts_with("the content is not a valid template, line 1: syntax error"), "{}", messages[1]);
+    }
+
+    #[test]
+    fn test_missing_location() {
+        let diagnostics = parse_template("project:\n  children:\n    - a", "t.yaml", None).unwrap_err();
+        let messages: Vec<&str> = diagnostics.0.iter().map(|d| d.message.as_str()).collect();
+        // the template cannot be read without it, the missing `project` it leaves is not reported again
+        assert_eq!(messages, vec!["`project` has no `default_location`"]);
+        assert_eq!(diagnostics.0[0].help.as_deref(), Some("add `default_location:` to `project`"));
+
+        let diagnostics = parse_template("version: 3\nproject:\n  default_location: ~/x\n", "t.yaml", None).unwrap_err();
+        assert_eq!(diagnostics.0[0].message, "template version 3 is not supported");
+    }
+
+    #[test]
+    fn test_positions_after_pruning() {
+        let source = "\
+project:
+  default_location: ~/x
+  children:
+    - 42
+    - [a]
+    - \"{{Nope}}\"
+    - Stems:
+        options: []
+";
+        let diagnostics = parse_template(source, "t.yaml", None).unwrap_err();
+        let lines: Vec<(usize, &str)> = diagnostics.0.iter().map(|d| (d.span.unwrap().line, d.message.as_str())).collect();
+        assert_eq!(lines, vec![
+            (4, "invalid type: integer `42`, expected a folder name or a single `Name: {...}` mapping"),
+            (5, "invalid type: sequence, expected a folder name or a single `Name: {...}` mapping"),
+            (8, "the list of choices is empty, add at least one choice or remove the key"),
+            // the entries above are cut out before the references are checked
+            (6, "unknown reference `{{Nope}}`"),
+        ]);
+    }
+}
@@ -0,0 +1,151 @@
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::path::PathBuf;
+use user_error::{UFE, UserFacingError};
+use crate::backend::folder::Rollback;
+use crate::backend::yaml::diagnostic::Diagnostics;
+
+/// Everything that can go wrong between reading the template and building the folders.
+///
+/// Every variant maps to its own exit code, so scripts can tell them apart.
+#[derive(Debug)]
+pub enum Error {
+    /// The command line arguments are invalid.
+    Usage(String),
+    /// The template file could not be read.
+    TemplateRead { path: PathBuf, source: io::Error },
+    /// The template could not be parsed or contains invalid entries.
+    TemplateParse(Diagnostics),
+    /// The answers file could not be read.
+    Answers { path: PathBuf, message: String },
+    /// The answers do not describe a complete project, e.g. a selection is missing.
+    Validation(String),
+    /// A folder could not be created.
+    Filesystem { path: PathBuf, source: io::Error },
+    /// Building stopped halfway, what it had created up to then was removed again.
+    Build { path: PathBuf, source: io::Error, rollback: Rollback },
+    /// Folders or files of the project already exist and the conflict policy is to stop.
+    Conflict(Vec<PathBuf>),
+    /// The project path could not be copied to the clipboard.
+    Clipboard(String),
+    /// The terminal ui could not be set up or drawn.
+    Terminal(io::Error),
+    /// The user quit without saving.
+    Cancelled,
+}
+
+impl Error {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Usage(_) => 2,
+            Error::TemplateRead { .. } => 3,
+            Error::TemplateParse(_) => 4,
+            Error::Validation(_) => 5,
+            Error::Filesystem { .. } | Error::Build { .. } => 6,
+            Error::Clipboard(_) => 7,
+            Error::Terminal(_) => 8,
+            Error::Answers { .. } => 9,
+            Error::Conflict(_) => 10,
+            Error::Cancelled => 130,
+        }
+    }
+
+    /// Prints the error to stderr in the same style as the rest of the cli output.
+    pub fn print(&self) {
+        match self {
+            Error::Usage(message) => {
+                UserFacingError::new(message)
+                    .help(crate::front_end::cli::USAGE)
+                    .print();
+            }
+            Error::TemplateRead { path, source } => {
+                UserFacingError::new(format!("Could not read template {}", path.display()))
+                    .reason(source.to_string())
+                    .help("pass the template to use with --template <FILE>")
+                    .print();
+            }
+            Error::TemplateParse(diagnostics) => {
+                UserFacingError::new("Could not load template")
+                    .reason(format!("found {} problem(s) in the template", diagnostics.len()))
+                    .print();
+                eprint!("\n{}", diagnostics);
+            }
+            Error::Validation(message) => {
+                // the first line sums the problem up, further lines list the details
+                let mut lines = message.lines();
+                let mut error = UserFacingError::new(lines.next().unwrap_or_default());
+                for line in lines {
+                    error = error.reason(line);
+                }
+                error.print();
+            }
+            Error::Conflict(paths) => {
+                let mut error = UserFacingError::new(format!("{} path(s) of the project already exist, nothing was created", paths.len()));
+                for path in paths {
+                    error = error.reason(path.display().to_string());
+                }
+                error.help("pass --on-conflict merge or suffix, or set `on_conflict` in the project section of the template")
+                    .print();
+            }
+            Error::Build { path, source, rollback } => {
+                let mut error = UserFacingError::new(format!("Building the project failed at {}", path.display()))
+                    .reason(source.to_string());
+                if rollback.is_clean() {
+                    error = error.reason(format!("rolled back, the {} path(s) created before were removed", rollback.removed.len()));
+                } else {
+                    error = error.reason(format!("rollback incomplete, {} path(s) could not be removed", rollback.left.len()));
+                    for (path, source) in &rollback.left {
+                        error = error.reason(format!("{}: {}", path.display(), source));
+                    }
+                }
+                error.print();
+            }
+            Error::Cancelled => eprintln!("Cancelled, nothing was created"),
+            other => UserFacingError::new(other.to_string()).print(),
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Usage(message) => write!(f, "{}", message),
+            Error::TemplateRead { path, source } => write!(f, "could not read template {}: {}", path.display(), source),
+            Error::TemplateParse(diagnostics) => write!(f, "{}", diagnostics),
+            Error::Answers { path, message } => write!(f, "could not read answers {}: {}", path.display(), message),
+            Error::Validation(message) => write!(f, "{}", message),
+            Error::Filesystem { path, source } => write!(f, "could not create {}: {}", path.display(), source),
+            Error::Build { path, source, rollback } => {
+                write!(f, "build failed at {}: {}", path.display(), source)?;
+                match rollback.is_clean() {
+                    true => write!(f, ", rolled back cleanly"),
+                    false => write!(f, ", {} path(s) could not be rolled back", rollback.left.len()),
+                }
+            }
+            Error::Conflict(paths) => {
+                let paths: Vec<String> = paths.iter().map(|p| p.display().to_string()).collect();
+                write!(f, "already exists: {}", paths.join(", "))
+            }
+            Error::Clipboard(message) => write!(f, "could not copy the project path to the clipboard: {}", message),
+            Error::Terminal(source) => write!(f, "terminal error: {}", source),
+            Error::Cancelled => write!(f, "cancelled by the user"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::TemplateRead { source, .. } | Error::Filesystem { source, .. } | Error::Build { source, .. } | Error::Terminal(source) => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<Diagnostics> for Error {
+    fn from(diagnostics: Diagnostics) -> Self {
+        Error::TemplateParse(diagnostics)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
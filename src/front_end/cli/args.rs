@@ -0,0 +1,290 @@
+use std::path::{Path, PathBuf};
+use crate::backend::folder::{ConflictPolicy, POLICY_NAMES};
+
+pub const USAGE: &str = "\
+Usage: scaffolder [COMMAND] [OPTIONS]
+
+Commands:
+  show                    print the template with its includes and the templates it extends merged in
+  templates               list the templates that can be used and the files they come from
+
+Options:
+  -t, --template <PATH>   template file, or a directory of template files
+                          [default: searched, see below]
+      --template-name <NAME>
+                          which of several templates to use, skips picking one in the terminal ui
+  -l, --location <PATH>   override the default_location of the template
+      --on-conflict <POLICY>
+                          what to do when the project already exists: abort, merge, suffix or prompt
+                          [default: on_conflict of the template, else prompt, abort without the ui]
+  -a, --answers <FILE>    read the answers from a yaml or json file instead of asking
+  -s, --set <NAME=VALUE>  answer a question by node name or path, can be repeated
+                          [repeat it to check several items of a multi-select option]
+  -r, --record[=<FILE>]   save the given answers to replay the run with --answers
+                          [default: .scaffolder-answers.yaml in the project folder]
+      --no-clipboard      do not copy the project path to the clipboard
+  -n, --dry-run           print the steps of the build as a tree instead of building
+      --json              print the steps of --dry-run as json
+  -h, --help              print this help
+
+Passing --answers or --set skips the terminal ui, every question has to be answered then.
+
+Without --template the templates are searched in, a template hides later ones with its name:
+  1. the template file or directory in $SCAFFOLDER_TEMPLATE
+  2. ./template.yaml
+  3. the closest .scaffolder/ directory in the current directory or one of its parents
+  4. $XDG_CONFIG_HOME/scaffolder/templates, or ~/.config/scaffolder/templates";
+
+/// What to do with the template, building a project unless a command is given.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Command {
+    #[default]
+    Build,
+    Show,
+    Templates,
+}
+
+/// Where `--record` saves the answers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Record {
+    /// In the project folder, built with the rest of the project.
+    Project,
+    File(PathBuf),
+}
+
+pub struct Args {
+    pub command: Command,
+    /// Searched for when not given.
+    pub template: Option<PathBuf>,
+    pub template_name: Option<String>,
+    pub location: Option<String>,
+    pub on_conflict: Option<ConflictPolicy>,
+    pub answers: Option<PathBuf>,
+    pub set: Vec<(String, String)>,
+    pub record: Option<Record>,
+    pub no_clipboard: bool,
+    pub dry_run: bool,
+    /// Prints the plan of a dry run as json.
+    pub json: bool,
+    pub help: bool,
+}
+
+impl Args {
+    /// Whether the answers come from the command line instead of the terminal ui.
+    pub fn is_non_interactive(&self) -> bool {
+        self.answers.is_some() || !self.set.is_empty()
+    }
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Args {
+            command: Command::Build,
+            template: None,
+            template_name: None,
+            location: None,
+            on_conflict: None,
+            answers: None,
+            set: Vec::new(),
+            record: None,
+            no_clipboard: false,
+            dry_run: false,
+            json: false,
+            help: false,
+        }
+    }
+}
+
+/// The options that take a value.
+const VALUE_FLAGS: &[&str] = &[
+    "-t", "--template", "--template-name", "-l", "--location", "--on-conflict", "-a", "--answers", "-s", "--set",
+];
+
+/// The options whose value can only be given as `--flag=value`.
+const OPTIONAL_VALUE_FLAGS: &[&str] = &["-r", "--record"];
+
+/// The options that are only switched on.
+const SWITCHES: &[&str] = &["--no-clipboard", "-n", "--dry-run", "--json", "-h", "--help"];
+
+/// Splits `--flag=value` and `-f=value` of a known option into the option and its value.
+fn split_flag(arg: &str) -> (&str, Option<&str>) {
+    match arg.split_once('=') {
+        Some((flag, value)) if is_known(flag) => (flag, Some(value)),
+        _ => (arg, None),
+    }
+}
+
+pub fn parse<I: Iterator<Item = String>>(mut raw_args: I) -> Result<Args, String> {
+    let mut args = Args::default();
+    let mut command_set = false;
+
+    while let Some(arg) = raw_args.next() {
+        // support both `--flag value` and `--flag=value`
+        let (flag, inline_value) = split_flag(&arg);
+        let (flag, inline_value) = (flag.to_string(), inline_value.map(str::to_string));
+        if inline_value.is_some() && SWITCHES.contains(&flag.as_str()) {
+            return Err(format!("'{}' takes no value", flag));
+        }
+        // values can start with a `-`, only a known option in its place means it is missing
+        let mut value_for = |flag: &str| -> Result<String, String> {
+            if let Some(value) = inline_value.clone() {
+                return Ok(value);
+            }
+            match raw_args.next() {
+                Some(value) if !is_flag(&value) => Ok(value),
+                _ => Err(format!("missing value for '{}'", flag)),
+            }
+        };
+
+        match flag.as_str() {
+            "-t" | "--template" => {
+                args.template = Some(PathBuf::from(value_for(&flag)?));
+            }
+            "--template-name" => args.template_name = Some(value_for(&flag)?),
+            "-l" | "--location" => args.location = Some(value_for(&flag)?),
+            "--on-conflict" => {
+                let policy = value_for(&flag)?;
+                args.on_conflict = Some(ConflictPolicy::from_name(&policy)
+                    .ok_or_else(|| format!("unknown conflict policy '{}', use one of {}", policy, POLICY_NAMES.join(", ")))?);
+            }
+            "-a" | "--answers" => args.answers = Some(PathBuf::from(value_for(&flag)?)),
+            "-s" | "--set" => {
+                let assignment = value_for(&flag)?;
+                let (name, value) = assignment.split_once('=')
+                    .ok_or_else(|| format!("'{}' expects NAME=VALUE, got '{}'", flag, assignment))?;
+                args.set.push((name.to_string(), value.to_string()));
+            }
+            "-r" | "--record" => {
+                args.record = Some(match inline_value.as_deref() {
+                    None => Record::Project,
+                    Some("") => return Err(format!("missing value for '{}'", flag)),
+                    Some(path) => Record::File(PathBuf::from(path)),
+                });
+            }
+            "--no-clipboard" => args.no_clipboard = true,
+            "-n" | "--dry-run" => args.dry_run = true,
+            "--json" => args.json = true,
+            "-h" | "--help" => args.help = true,
+            "show" | "templates" if !command_set => {
+                args.command = if flag == "show" { Command::Show } else { Command::Templates };
+                command_set = true;
+            }
+            _ if flag.starts_with('-') => return Err(format!("unknown option '{}'", flag)),
+            _ => return Err(format!("unexpected argument '{}'", flag)),
+        }
+    }
+
+    if let Some(template) = &args.template {
+        if !args.help && !Path::new(template).exists() {
+            return Err(format!("template '{}' does not exist", template.display()));
+        }
+    }
+    if let Some(answers) = &args.answers {
+        if !args.help && !answers.is_file() {
+            return Err(format!("answers file '{}' does not exist", answers.display()));
+        }
+    }
+    if args.json && !args.dry_run {
+        return Err(String::from("'--json' only works with '--dry-run'"));
+    }
+    Ok(args)
+}
+
+fn is_flag(arg: &str) -> bool {
+    is_known(split_flag(arg).0)
+}
+
+fn is_known(flag: &str) -> bool {
+    VALUE_FLAGS.contains(&flag) || OPTIONAL_VALUE_FLAGS.contains(&flag) || SWITCHES.contains(&flag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(args: &[&str]) -> Result<Args, String> {
+        parse(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn test_defaults() {
+        let args = parse_str(&[]).unwrap();
+        assert!(args.template.is_none());
+        assert!(args.location.is_none());
+        assert!(!args.dry_run && !args.no_clipboard && !args.help);
+    }
+
+    #[test]
+    fn test_flags() {
+        let args = parse_str(&["--template", "structure.yaml", "-l=~/out", "--dry-run", "--no-clipboard"]).unwrap();
+        assert_eq!(args.location.as_deref(), Some("~/out"));
+        assert!(parse_str(&["--dry-run=yes"]).is_err());
+
+        let args = parse_str(&["--template=structure.yaml", "-l", "~/out", "-n", "--no-clipboard"]).unwrap();
+        assert_eq!(args.template, Some(PathBuf::from("structure.yaml")));
+        assert_eq!(args.location.as_deref(), Some("~/out"));
+        assert!(args.dry_run && args.no_clipboard);
+    }
+
+    #[test]
+    fn test_set() {
+        let args = parse_str(&["--set", "ProjectName=My Song", "-s", "ProjectType/Output=a=b"]).unwrap();
+        assert!(args.is_non_interactive());
+        assert_eq!(args.set, vec![
+            (String::from("ProjectName"), String::from("My Song")),
+            (String::from("ProjectType/Output"), String::from("a=b")),
+        ]);
+        assert!(parse_str(&["--set", "ProjectName"]).is_err());
+
+        // values can start with a dash, a known option cannot be a value
+        let args = parse_str(&["-s", "Gain=-3", "-l", "-drafts"]).unwrap();
+        assert_eq!(args.set, vec![(String::from("Gain"), String::from("-3"))]);
+        assert_eq!(args.location.as_deref(), Some("-drafts"));
+        assert!(parse_str(&["--location", "--dry-run"]).is_err());
+        assert!(parse_str(&["--location", "--set=a=b"]).is_err());
+    }
+
+    #[test]
+    fn test_record() {
+        assert!(parse_str(&[]).unwrap().record.is_none());
+        assert_eq!(parse_str(&["--record"]).unwrap().record, Some(Record::Project));
+        assert_eq!(parse_str(&["-r=answers.yaml"]).unwrap().record, Some(Record::File(PathBuf::from("answers.yaml"))));
+        // the file is only taken with `=`, a word after the flag is an argument of its own
+        assert_eq!(parse_str(&["--record", "-n"]).unwrap().record, Some(Record::Project));
+        assert!(parse_str(&["--record", "answers.yaml"]).is_err());
+        assert!(parse_str(&["--record="]).is_err());
+    }
+
+    #[test]
+    fn test_commands() {
+        assert_eq!(parse_str(&[]).unwrap().command, Command::Build);
+        let args = parse_str(&["show", "--template", "test/include.yaml"]).unwrap();
+        assert_eq!(args.command, Command::Show);
+        assert_eq!(parse_str(&["templates"]).unwrap().command, Command::Templates);
+        assert!(parse_str(&["show", "show"]).is_err());
+    }
+
+    #[test]
+    fn test_template_name() {
+        let args = parse_str(&["-t", "test/catalog", "--template-name", "podcast"]).unwrap();
+        assert_eq!(args.template, Some(PathBuf::from("test/catalog")));
+        assert_eq!(args.template_name.as_deref(), Some("podcast"));
+        assert!(parse_str(&["--template-name"]).is_err());
+    }
+
+    #[test]
+    fn test_on_conflict() {
+        assert_eq!(parse_str(&["--on-conflict", "suffix"]).unwrap().on_conflict, Some(ConflictPolicy::Suffix));
+        assert_eq!(parse_str(&["--on-conflict=merge"]).unwrap().on_conflict, Some(ConflictPolicy::Merge));
+        assert!(parse_str(&[]).unwrap().on_conflict.is_none());
+        assert!(parse_str(&["--on-conflict", "overwrite"]).is_err());
+    }
+
+    #[test]
+    fn test_usage_errors() {
+        assert!(parse_str(&["--frobnicate"]).is_err());
+        assert!(parse_str(&["--json"]).is_err());
+        assert!(parse_str(&["--location"]).is_err());
+        assert!(parse_str(&["--template", "does/not/exist.yaml"]).is_err());
+    }
+}
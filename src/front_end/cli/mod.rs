@@ -0,0 +1,19 @@
+use crate::error::{Error, Result};
+
+use std::path::PathBuf;
+use crate::backend::folder::ConflictPolicy;
+
+pub(crate) mod args;
+mod prompt;
+
+pub use args::{Args, Command, Record, USAGE};
+
+/// Parses the command line arguments of the current process.
+pub fn parse_args() -> Result<Args> {
+    args::parse(std::env::args().skip(1)).map_err(Error::Usage)
+}
+
+/// Asks on the terminal how to build a project that already exists.
+pub fn ask_conflict_policy(conflicts: &[PathBuf]) -> Result<ConflictPolicy> {
+    prompt::ask_conflict_policy(&mut std::io::stdin().lock(), &mut std::io::stderr(), conflicts).map_err(Error::Terminal)
+}
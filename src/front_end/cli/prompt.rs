@@ -0,0 +1,43 @@
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use crate::backend::folder::ConflictPolicy;
+
+/// Asks how to build a project whose folders are already on disk, `Abort` if the input ends.
+pub fn ask_conflict_policy<R: BufRead, W: Write>(input: &mut R, output: &mut W, conflicts: &[PathBuf]) -> io::Result<ConflictPolicy> {
+    writeln!(output, "Already there:")?;
+    for path in conflicts {
+        writeln!(output, "  {}", path.display())?;
+    }
+    loop {
+        write!(output, "[a]bort, [m]erge into it or build next to it with a [s]uffix? ")?;
+        output.flush()?;
+        let mut answer = String::new();
+        if input.read_line(&mut answer)? == 0 {
+            return Ok(ConflictPolicy::Abort);
+        }
+        match answer.trim().to_lowercase().as_str() {
+            "a" | "abort" => return Ok(ConflictPolicy::Abort),
+            "m" | "merge" => return Ok(ConflictPolicy::Merge),
+            "s" | "suffix" => return Ok(ConflictPolicy::Suffix),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ask_conflict_policy() {
+        let conflicts = vec![PathBuf::from("/tmp/Song/Tune")];
+        let mut output = Vec::new();
+        let policy = ask_conflict_policy(&mut "x\nS\n".as_bytes(), &mut output, &conflicts).unwrap();
+        assert_eq!(policy, ConflictPolicy::Suffix);
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("Already there:\n  /tmp/Song/Tune\n"), "{}", output);
+        assert_eq!(output.matches("[a]bort").count(), 2);
+
+        assert_eq!(ask_conflict_policy(&mut "".as_bytes(), &mut Vec::new(), &conflicts).unwrap(), ConflictPolicy::Abort);
+    }
+}
@@ -1,2 +1,3 @@
-
-pub mod ui;
\ No newline at end of file
+pub mod cli;
+#[cfg(feature = "tui")]
+pub mod ui;
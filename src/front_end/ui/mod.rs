@@ -1,9 +1,17 @@
-use std::io;
 use crate::backend::tree::nodes::LeafNodeType;
+use crate::backend::yaml::TemplateEntry;
+use crate::error::Result;
 use slab_tree::*;
 
+#[allow(clippy::module_inception)]
 pub(crate) mod ui;
 
-pub fn init_ui(tree: Tree<LeafNodeType>) -> Result<Option<Tree<String>>, io::Error>{
+/// Asks the questions of `tree` and returns it with all answers once the user saves.
+pub fn init_ui(tree: Tree<LeafNodeType>) -> Result<Tree<LeafNodeType>> {
     ui::init_ui(tree)
 }
+
+/// Lets the user pick one of several templates before the questions are asked.
+pub fn pick_template(templates: &[TemplateEntry]) -> Result<TemplateEntry> {
+    ui::pick_template(templates)
+}
@@ -1,8 +1,10 @@
 use std::{
     io,
+    path::{Path, PathBuf},
 };
 
 use crossterm::{
+    cursor::Show,
     event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
@@ -11,83 +13,24 @@ use crossterm::{
 use ratatui::{prelude::*, widgets::*};
 use ratatui::widgets::block::{Position, Title};
 use slab_tree::*;
-use crate::backend::tree::nodes::{LeafNodeType, CloneTree, NodeIndex};
-
+use crate::backend::folder;
+use crate::backend::folder::Status;
+use crate::backend::tree::condition::NodeEnabled;
+use crate::backend::tree::scope::{copies, Scope};
+use crate::backend::tree::nodes::{LeafNodeType, CloneTree};
+use crate::backend::tree::resolve::{is_unique, resolve_tree};
+use crate::backend::tree::substitute;
+use crate::backend::tree::transform::transform;
+use crate::backend::yaml::TemplateEntry;
+use crate::error::{Error, Result};
+
+const HINT: &str = "Press ESC to exit i mode and h for help";
 
 enum InputMode {
     Normal,
     Editing,
 }
 
-pub struct StatefulList {
-    state: ListState,
-    items: Vec<(String, bool)>,
-    multiselect: bool,
-}
-impl StatefulList {
-    pub fn with_items(items: Vec<(String, bool)>, multiselect: bool) -> StatefulList {
-        StatefulList {
-            state: ListState::default().with_selected(Some(0)),
-            items,
-            multiselect,
-        }
-    }
-    pub fn join_names_with(&self, separator: &str) -> String {
-        let vec: Vec<String> = self.items.iter().map(|(name, _)| name.clone()).collect();
-        return vec.join(separator);
-    }
-    pub fn clone(&self) -> StatefulList {
-        return StatefulList { state: self.state.clone(), items: self.items.clone(), multiselect: self.multiselect.clone() };
-    }
-    fn next(&mut self) {
-        let i = match self.state.selected() {
-            Some(i) => (i + 1) % self.items.len(),
-            None => 0,
-        };
-        self.state.select(Some(i));
-    }
-    fn previous(&mut self) {
-        let i = match self.state.selected() {
-            Some(i) => {
-                if i == 0 {
-                    self.items.len() - 1
-                } else {
-                    i - 1
-                }
-            }
-            None => 0,
-        };
-        self.state.select(Some(i));
-    }
-    fn mark_item(&mut self) -> Option<String> {
-        let item_pos = self.state.selected();
-        match item_pos {
-            Some(_) => {
-                //if not multiselect remove clear selection
-                if !self.multiselect {
-                    for i in self.items.iter_mut() { i.1 = false; }
-                }
-                if let Some(pos) = item_pos {
-                    //mark current item
-                    self.items[pos].1 = !self.items[pos].1;
-                    return None;
-                }
-
-                Some(String::from("invalid selection"))
-
-            }
-            None => { Some(String::from("please make a selection")) }
-        }
-    }
-    fn get_selected(&self) -> Option<String> {
-        for item in self.items.clone() {
-            if item.1 {
-                return Some(item.0)
-            }
-        }
-        return None
-    }
-}
 enum WindowType {
     Help,
     App,
@@ -102,29 +45,55 @@ struct App {
     node_id: NodeId,
     tree: Tree<LeafNodeType>,
     preview_tree: Tree<LeafNodeType>,
-    vertical_index: usize,
+    /// The question node every line of the preview belongs to, copies share their node.
+    preview_sources: Vec<NodeId>,
+    /// Whether the folder or file of every line of the preview is already on disk.
+    preview_statuses: Vec<Status>,
     output: String,
 }
 impl App {
-    fn new(tree: Tree<LeafNodeType>) -> App {
-        let node_id = tree.root_id().expect("tree has no root");
-        App {
+    fn new(tree: Tree<LeafNodeType>) -> Result<App> {
+        let node_id = tree.root_id().ok_or_else(|| Error::Validation(String::from("the template is empty")))?;
+        Ok(App {
             window: WindowType::App,
             question: String::new(),
-            vertical_index: 0,
+            preview_sources: Vec::new(),
+            preview_statuses: Vec::new(),
             preview_tree: tree.clone(),
             tree,
             node_id,
             input_mode: InputMode::Normal,
             cursor_position: 0,
-            output: String::from("Press ESC to exit i mode and h for help"),
+            output: String::from(HINT),
+        })
+    }
+    /// The first rule the text of the current input breaks, shown in the output line.
+    fn input_error(&self) -> Option<String> {
+        match self.tree.get(self.node_id)?.data() {
+            LeafNodeType::TextInput { name, input, rules, transform: transforms, .. } => {
+                rules.check(&transform(transforms, input)).err().map(|e| format!("{}: {}", name, e))
+            }
+            _ => None,
+        }
+    }
+    /// Puts a broken rule of the current input in the output line, or clears the last one.
+    fn input_is_valid(&mut self) -> bool {
+        match self.input_error() {
+            Some(error) => {
+                self.output = error;
+                false
+            }
+            None => {
+                self.output = String::from(HINT);
+                true
+            }
         }
     }
     fn data_cloned(&self) -> Option<LeafNodeType> {
         if let Some(node) = self.tree.get(self.node_id) {
             return Some(node.data().clone());
         }
-        return None;
+        None
     }
     fn move_cursor_left(&mut self) {
         let cursor_moved_left = self.cursor_position.saturating_sub(1);
@@ -136,37 +105,28 @@ impl App {
     }
     fn enter_char(&mut self, new_char: char) {
         let node_opt = self.tree.get_mut(self.node_id);
-        match node_opt {
-            Some(mut node) => {
-                match node.data() {
-                    LeafNodeType::TextInput { name: _name, input } => {
-                        input.insert(self.cursor_position, new_char);
-                        self.move_cursor_right();
-                    }
-                    _ => {}
-                }
+        if let Some(mut node) = node_opt {
+            if let LeafNodeType::TextInput { name: _name, input, .. } = node.data() {
+                input.insert(self.cursor_position, new_char);
+                self.move_cursor_right();
             }
-            None => {}
         }
     }
     fn delete_char(&mut self) {
         if let Some(mut node) = self.tree.get_mut(self.node_id) {
-            match node.data() {
-                LeafNodeType::TextInput { name: _name, input } => {
-                    let is_not_cursor_leftmost = self.cursor_position != 0;
-                    if is_not_cursor_leftmost {
-                        input.remove(self.cursor_position - 1);
-                        self.move_cursor_left();
-                    }
+            if let LeafNodeType::TextInput { name: _name, input, .. } = node.data() {
+                let is_not_cursor_leftmost = self.cursor_position != 0;
+                if is_not_cursor_leftmost {
+                    input.remove(self.cursor_position - 1);
+                    self.move_cursor_left();
                 }
-                _ => {}
             }
         }
     }
     fn clamp_cursor(&mut self, new_cursor_pos: usize) -> usize {
         if let Some(data) = self.data_cloned() {
             match data {
-                LeafNodeType::TextInput { name: _name, input } => { new_cursor_pos.clamp(0, input.len()) }
+                LeafNodeType::TextInput { name: _name, input, .. } => { new_cursor_pos.clamp(0, input.len()) }
                 _ => { 1 }
             }
         } else { 0 }
@@ -175,68 +135,57 @@ impl App {
         self.cursor_position = 0;
     }
     fn cursor_end(&mut self) {
-        if let Some(data) = self.data_cloned() {
-            match data {
-                LeafNodeType::TextInput { name: _name, input } => {
-                    self.cursor_position = input.len();
-                }
-                _ => {}
-            }
+        if let Some(LeafNodeType::TextInput { name: _name, input, .. }) = self.data_cloned() {
+            self.cursor_position = input.len();
         }
     }
-    fn next_item(&mut self, skip_child: bool) -> Option<Tree<String>> {
+    /// Moves to the next question, returns true once the end is reached with every question answered.
+    fn next_item(&mut self, skip_child: bool) -> bool {
         fn check_recursively(app: &mut App, skip_child: bool, end_reached: &mut bool) {
             if let Some(node) = app.tree.get(app.node_id) {
-                let first_child_opt = node.first_child();
-                if !skip_child && first_child_opt.is_some() {
-                    let child = first_child_opt.expect("Error, next child data could not be unwrap, despite it existing");
+                let first_child_opt = if skip_child { None } else { node.first_child() };
+                if let Some(child) = first_child_opt {
                     app.node_id = child.node_id();
-                    match child.data() {
-                        LeafNodeType::Text { name: _name } => {
-                            check_recursively(app, false, end_reached);
-                        }
-                        _ => {}
+                    if !app.tree.is_enabled(child.node_id()) {
+                        // a branch whose `when` does not match is skipped with its children
+                        check_recursively(app, true, end_reached);
+                    } else if child.data().is_fixed() {
+                        check_recursively(app, false, end_reached);
                     }
                     app.set_question();
                 } else if let Some(sibling) = node.next_sibling() {
                     app.node_id = sibling.node_id();
-                    match sibling.data() {
-                        LeafNodeType::Text { name: _name } => {
-                            check_recursively(app, false, end_reached);
-                        }
-                        _ => {}
+                    if !app.tree.is_enabled(sibling.node_id()) {
+                        check_recursively(app, true, end_reached);
+                    } else if sibling.data().is_fixed() {
+                        check_recursively(app, false, end_reached);
                     }
                     app.set_question();
                 } else if let Some(parent) = node.parent() {
                     app.node_id = parent.node_id();
                     check_recursively(app, true, end_reached);
                 } else {
-                    app.vertical_index = app.tree.node_index(app.node_id);
                     *end_reached = true;
-
                 }
-                app.vertical_index = app.tree.node_index(app.node_id);
             }
         }
         let mut end_reached = false;
         check_recursively(self, skip_child, &mut end_reached);
 
-        if end_reached {
-            check_tree(&self.preview_tree)
-        } else {
-            None
-        }
+        end_reached && resolve_tree(&self.tree).is_ok()
 
     }
     fn previous_item(&mut self) {
         if let Some(node) = self.tree.get(self.node_id) {
-            if let Some(sibling) = node.prev_sibling() {
+            let mut prev_sibling = node.prev_sibling().map(|s| s.node_id());
+            while let Some(sibling_id) = prev_sibling.filter(|id| !self.tree.is_enabled(*id)) {
+                prev_sibling = self.tree.get(sibling_id).and_then(|s| s.prev_sibling().map(|p| p.node_id()));
+            }
+            if let Some(sibling) = prev_sibling.and_then(|id| self.tree.get(id)) {
                 self.node_id = sibling.node_id();
-                self.vertical_index = self.tree.node_index(self.node_id);
                 self.set_question();
             } else if let Some(parent) = node.parent() {
                 self.node_id = parent.node_id();
-                self.vertical_index = self.tree.node_index(self.node_id);
                 self.set_question();
             }
         }
@@ -244,10 +193,13 @@ impl App {
     fn set_question(&mut self) {
         if let Some(node) = self.tree.get(self.node_id) {
             match node.data() {
-                LeafNodeType::TextInput { name, input: _input } => {
+                LeafNodeType::TextInput { name, input: _input, .. } => {
                     self.question = String::from("Type in a ") + name.as_str();
                 }
-                LeafNodeType::Option { name, options: _options } => {
+                LeafNodeType::Option { name, options, .. } if options.is_multiselect() => {
+                    self.question = String::from("Check the options for ") + name.as_str() + ", → to continue"
+                }
+                LeafNodeType::Option { name, options: _options, .. } => {
                     self.question = String::from("Chose an option for ") + name.as_str()
                 }
                 _ => {}
@@ -257,7 +209,7 @@ impl App {
     fn set_editing_mode(&mut self) {
         if let Some(node) = self.tree.get(self.node_id) {
             match node.data() {
-                LeafNodeType::TextInput {name: _name, input:_input} => {
+                LeafNodeType::TextInput {name: _name, input:_input, .. } => {
                     self.input_mode = InputMode::Editing;
                     self.cursor_end();
                 }
@@ -266,84 +218,138 @@ impl App {
         }
     }
     fn update_preview_tree(&mut self) {
-        fn walk_tree(node: NodeRef<LeafNodeType>, cur_index: &mut usize, tree_index: usize, node_id: &mut NodeId) {
-            for child_ref in node.children() {
-                *cur_index += 1;
-                if *cur_index == tree_index {
-                    *node_id = child_ref.node_id();
-                    break
-                } else if *cur_index > tree_index {
-                    break
-                } else {
-                    walk_tree(child_ref, cur_index, tree_index, node_id);
+        // names can reference answers anywhere in the tree, so every node is refreshed,
+        // branches whose `when` does not match are left out and every checked item of a
+        // multi-select option is shown with its own copy of the children
+        // every line also gets its status on disk, the path is unknown below unresolved names
+        fn walk_tree(tree: &Tree<LeafNodeType>, node_id: NodeId, mut preview_node: NodeMut<LeafNodeType>, scope: &Scope, path: Option<&Path>,
+                     sources: &mut Vec<NodeId>, statuses: &mut Vec<Status>) {
+            let child_ids: Vec<NodeId> = tree.get(node_id).into_iter().flat_map(|n| n.children()).map(|c| c.node_id()).collect();
+            for child_id in child_ids {
+                for child_scope in copies(tree, child_id, scope) {
+                    let preview = preview_of(tree, child_id, false, &child_scope);
+                    let child_path = path.filter(|_| preview.is_fixed()).map(|p| p.join(preview.get_name()));
+                    let unique = tree.get(child_id).is_some_and(|c| is_unique(&c));
+                    sources.push(child_id);
+                    statuses.push(child_path.as_deref().map_or(Status::New, |p| folder::status(p, unique)));
+                    let preview_child = preview_node.append(preview);
+                    walk_tree(tree, child_id, preview_child, &child_scope, child_path.as_deref(), sources, statuses);
                 }
             }
         }
-        let mut node_id = NodeId::from(self.preview_tree.root_id().expect("Error, Preview tree has no root"));
-        walk_tree(self.preview_tree.root().expect("Error, tree has no root"), &mut 0, self.vertical_index, &mut node_id);
-        if let Some(mut node) = self.preview_tree.get_mut(node_id) {
-            match self.tree.get(self.node_id).expect("Error, no node to nodeID").data() {
-                LeafNodeType::Text {name: _name} => {}
-                LeafNodeType::TextInput {name: _name, input} => {
-                    if !input.is_empty() {
-                        *node.data() = LeafNodeType::Text {name: input.clone()}
-                    }
-                }
-                LeafNodeType::Option {name: _name, options} => {
-                    if let Some(item) = options.get_selected() {
-                        *node.data() = LeafNodeType::Text{name: item}
-                    }
-                }
-            }
+        let root_id = self.tree.root_id().expect("Error, tree has no root");
+        let mut sources = vec![root_id];
+        let mut statuses = vec![Status::New];
+        let root = preview_of(&self.tree, root_id, true, &Scope::default());
+        let location = Some(PathBuf::from(root.get_name())).filter(|_| root.is_fixed());
+        let mut preview_tree = TreeBuilder::new().with_root(root).build();
+        walk_tree(&self.tree, root_id, preview_tree.root_mut().expect("Error, Preview tree has no root"), &Scope::default(),
+                  location.as_deref(), &mut sources, &mut statuses);
+        self.preview_tree = preview_tree;
+        self.preview_sources = sources;
+        self.preview_statuses = statuses;
+    }
+}
+
+/// How a node is shown in the preview, answered nodes show the name they will be built with.
+fn preview_of(tree: &Tree<LeafNodeType>, node_id: NodeId, is_root: bool, scope: &Scope) -> LeafNodeType {
+    let node = tree.get(node_id).expect("Error, no node to nodeID");
+    let Ok(Some(name)) = substitute::resolved_answer_in(tree, node_id, scope) else {
+        return node.data().clone();
+    };
+    match node.data() {
+        LeafNodeType::File { name: _name, content, mode, .. } => LeafNodeType::File { name, content: content.clone(), mode: *mode, when: None },
+        // the root holds the location, show it the way it will be built
+        LeafNodeType::TextInput { .. } if is_root => {
+            let name = folder::resolve_location(&name)
+                .map(|path| path.to_string_lossy().to_string())
+                .unwrap_or(name);
+            LeafNodeType::Text { name, when: None }
         }
+        _ => LeafNodeType::Text { name, when: None },
+    }
+}
 
+/// Restores the terminal when dropped, so it is also restored on errors and panics.
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter() -> io::Result<TerminalGuard> {
+        enable_raw_mode()?;
+        let guard = TerminalGuard;
+        execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture)?;
+        Ok(guard)
     }
 }
 
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = disable_raw_mode();
+        let _ = execute!(io::stdout(), LeaveAlternateScreen, DisableMouseCapture, Show);
+    }
+}
+
+pub fn init_ui(tree: Tree<LeafNodeType>) -> Result<Tree<LeafNodeType>> {
+    let mut app = App::new(tree)?;
 
-pub fn init_ui(tree: Tree<LeafNodeType>) -> Result<Option<Tree<String>>, io::Error> {
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+    let _guard = TerminalGuard::enter().map_err(Error::Terminal)?;
+    let backend = CrosstermBackend::new(io::stdout());
+    let mut terminal = Terminal::new(backend).map_err(Error::Terminal)?;
 
-    let mut app = App::new( tree);
     app.set_question();
     app.set_editing_mode();
-    let res = run_app(&mut terminal, app);
-
-    disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
-    terminal.show_cursor()?;
-    return res;
+    app.update_preview_tree();
+    run_app(&mut terminal, app)
+}
+
+pub fn pick_template(templates: &[TemplateEntry]) -> Result<TemplateEntry> {
+    if templates.is_empty() {
+        return Err(Error::Usage(String::from("there are no templates to pick from")));
+    }
+    let _guard = TerminalGuard::enter().map_err(Error::Terminal)?;
+    let backend = CrosstermBackend::new(io::stdout());
+    let mut terminal = Terminal::new(backend).map_err(Error::Terminal)?;
+
+    let mut state = ListState::default().with_selected(Some(0));
+    loop {
+        terminal.draw(|f| picker_ui(f, templates, &mut state)).map_err(Error::Terminal)?;
+
+        if let Event::Key(key) = event::read().map_err(Error::Terminal)? {
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            let selected = state.selected().unwrap_or(0);
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Err(Error::Cancelled),
+                KeyCode::Down => state.select(Some((selected + 1) % templates.len())),
+                KeyCode::Up => state.select(Some((selected + templates.len() - 1) % templates.len())),
+                KeyCode::Enter | KeyCode::Right => return Ok(templates[selected].clone()),
+                _ => {}
+            }
+        }
+    }
 }
 
-fn run_app<B: Backend>( terminal: &mut Terminal<B>, mut app: App, ) -> Result<Option<Tree<String>>, io::Error> {
+fn run_app<B: Backend>( terminal: &mut Terminal<B>, mut app: App, ) -> Result<Tree<LeafNodeType>> {
     loop {
-        terminal.draw(|f| ui(f, &mut app))?;
+        terminal.draw(|f| ui(f, &mut app)).map_err(Error::Terminal)?;
 
-        if let Event::Key(key) = event::read()? {
+        if let Event::Key(key) = event::read().map_err(Error::Terminal)? {
             match &app.window {
                 WindowType::App => {
                     match &app.input_mode {
                         InputMode::Normal if key.kind == KeyEventKind::Press => {
                             let mut node = app.tree.get_mut(app.node_id).expect("Unexpected Error, cannot get node from NodeID");
                             match node.data() {
-                                LeafNodeType::Option { options, name: _name } => {
+                                LeafNodeType::Option { options, name: _name, .. } => {
                                     match key.code {
-                                        KeyCode::Char('q') | KeyCode::Esc => return Ok(None),
+                                        KeyCode::Char('q') | KeyCode::Esc => return Err(Error::Cancelled),
                                         KeyCode::Char('h') => { app.window = WindowType::Help }
                                         KeyCode::Char('s') => {
                                             app.update_preview_tree();
-                                            match check_tree(&app.preview_tree){
-                                                Some(tree) => {
-                                                    return Ok(Some(tree))},
-                                                None => { app.output = String::from("Cannot save, selection still missing"); }
+                                            match resolve_tree(&app.tree) {
+                                                Ok(_) => return Ok(app.tree),
+                                                Err(e) => { app.output = e.to_string(); }
                                             }
                                         }
                                         KeyCode::Down => options.next(),
@@ -351,48 +357,42 @@ fn run_app<B: Backend>( terminal: &mut Terminal<B>, mut app: App, ) -> Result<Op
                                         KeyCode::Enter => {
                                             if let Some(err) = options.mark_item() {
                                                 app.output = err;
+                                            } else if options.is_multiselect() {
+                                                // several items can be checked, → moves on
+                                                app.update_preview_tree();
                                             } else {
                                                 app.update_preview_tree();
-                                                match app.next_item(false) {
-                                                    Some(tree) => {
-                                                        println!("here");
-                                                        return Ok(Some(tree)); },
-                                                    None => {}
+                                                if app.next_item(false) {
+                                                    return Ok(app.tree);
                                                 }
                                                 app.set_editing_mode();
                                             }
                                         },
                                         KeyCode::Right => {
                                             app.update_preview_tree();
-                                            match app.next_item(false) {
-                                                Some(tree) => return Ok(Some(tree)),
-                                                None => {}
-                                            }
+                                            if app.next_item(false) { return Ok(app.tree) }
                                         },
                                         KeyCode::Left => app.previous_item(),
                                         _ => {}
                                     }
                                 }
-                                LeafNodeType::TextInput { name: _name, input: _input } => match key.code {
+                                LeafNodeType::TextInput { name: _name, input: _input, .. } => match key.code {
                                     KeyCode::Char('i') | KeyCode::Char('e') | KeyCode::Down | KeyCode::Enter => {
                                         app.input_mode = InputMode::Editing;
                                         app.cursor_end();
                                     }
                                     KeyCode::Right => {
                                         app.update_preview_tree();
-                                        match app.next_item(false) {
-                                            Some(tree) => return Ok(Some(tree)),
-                                            None => {}
-                                        }
+                                        if app.input_is_valid() && app.next_item(false) { return Ok(app.tree) }
                                     },
                                     KeyCode::Left => app.previous_item(),
-                                    KeyCode::Char('q') | KeyCode::Esc => return Ok(None),
+                                    KeyCode::Char('q') | KeyCode::Esc => return Err(Error::Cancelled),
                                     KeyCode::Char('h') => { app.window = WindowType::Help }
                                     KeyCode::Char('s') => {
                                         app.update_preview_tree();
-                                        match check_tree(&app.preview_tree) {
-                                            Some(tree) => return Ok(Some(tree)),
-                                            None => {app.output = String::from("Cannot save, selection still missing");}
+                                        match resolve_tree(&app.tree) {
+                                            Ok(_) => return Ok(app.tree),
+                                            Err(e) => {app.output = e.to_string();}
                                         }
                                     }
                                     _ => {}
@@ -400,11 +400,11 @@ fn run_app<B: Backend>( terminal: &mut Terminal<B>, mut app: App, ) -> Result<Op
                                 _ => {
                                     match key.code {
                                         KeyCode::Char('h') => { app.window = WindowType::Help }
-                                        KeyCode::Char('q') | KeyCode::Esc => return Ok(None),
+                                        KeyCode::Char('q') | KeyCode::Esc => return Err(Error::Cancelled),
                                         KeyCode::Char('s') => {
-                                            match check_tree(&app.preview_tree) {
-                                                Some(tree) => return Ok(Some(tree)),
-                                                None => {app.output = String::from("Cannot save, selection still missing");}
+                                            match resolve_tree(&app.tree) {
+                                                Ok(_) => return Ok(app.tree),
+                                                Err(e) => {app.output = e.to_string();}
                                             }
                                         }
                                         KeyCode::Left => app.previous_item(),
@@ -417,20 +417,23 @@ fn run_app<B: Backend>( terminal: &mut Terminal<B>, mut app: App, ) -> Result<Op
                             KeyCode::Char(to_insert) => {
                                 if to_insert.is_ascii() {
                                     app.enter_char(to_insert);
+                                    app.update_preview_tree();
                                 } else {
                                     app.output = String::from("Non-ASCII char is not allowed");
                                 }
                             }
                             KeyCode::Enter => {
-                                app.input_mode = InputMode::Normal;
                                 app.update_preview_tree();
-                                match app.next_item(false) {
-                                    Some(tree) => return Ok(Some(tree)),
-                                    None => {}
-                                }
+                                // the input stays open until its text passes the rules
+                                if !app.input_is_valid() { continue }
+                                app.input_mode = InputMode::Normal;
+                                if app.next_item(false) { return Ok(app.tree) }
                                 app.set_editing_mode();
                             }
-                            KeyCode::Backspace => { app.delete_char(); }
+                            KeyCode::Backspace => {
+                                app.delete_char();
+                                app.update_preview_tree();
+                            }
                             KeyCode::Left => { app.move_cursor_left(); }
                             KeyCode::Right => { app.move_cursor_right(); }
                             KeyCode::Esc | KeyCode::Up => { app.input_mode = InputMode::Normal; }
@@ -444,7 +447,7 @@ fn run_app<B: Backend>( terminal: &mut Terminal<B>, mut app: App, ) -> Result<Op
                 WindowType::Help => {
                     if key.kind == KeyEventKind::Press {
                         match key.code {
-                            KeyCode::Char('q')  => return Ok(None),
+                            KeyCode::Char('q')  => return Err(Error::Cancelled),
                             _ => { app.window = WindowType::App }
                         }
                     }
@@ -454,40 +457,8 @@ fn run_app<B: Backend>( terminal: &mut Terminal<B>, mut app: App, ) -> Result<Op
     }
 }
 
-fn check_tree(tree: &Tree<LeafNodeType>) -> Option<Tree<String>> {
-
-    let root_string = tree.root().expect("Error tree has no root").data().get_name();
-
-    let mut string_tree: Tree<String> = Tree::new();
-    let root = string_tree.set_root(String::from(root_string));
-
-    let mut partly_empty = false;
-    fn walk_tree(node: NodeRef<LeafNodeType>, mut output_node: NodeMut<String>, partly_empty: &mut bool) {
-        for child in node.children() {
-            match child.data() {
-                LeafNodeType::Text {name} => {
-                    if name.is_empty() {
-                        *partly_empty = true;
-                    }
-                    let string_node = output_node.append(String::from(name));
-                    walk_tree(child, string_node, partly_empty);
-                }
-                _ => {
-                    *partly_empty = true;
-                }
-            }
-        }
-    }
-    walk_tree(tree.root().expect("Error, tree has no root"), string_tree.get_mut(root).unwrap(), &mut partly_empty);
-    if partly_empty {
-        None
-    } else {
-        Some(string_tree)
-    }
-}
-
-fn ui(f: &mut Frame, app: &mut App) {
-
+/// The title bar, the screen and the status line below it.
+fn main_layout(f: &mut Frame) -> std::rc::Rc<[Rect]> {
     let main_layout = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
@@ -507,6 +478,55 @@ fn ui(f: &mut Frame, app: &mut App) {
             ),
         main_layout[0],
     );
+    main_layout
+}
+
+fn picker_ui(f: &mut Frame, templates: &[TemplateEntry], state: &mut ListState) {
+    let main_layout = main_layout(f);
+    let inner_layout = Layout::default()
+        .direction(Direction::Horizontal)
+        .margin(1)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(main_layout[1]);
+
+    let items: Vec<ListItem> = templates.iter()
+        .map(|t| {
+            let mut lines = vec![Line::from(t.name.as_str().bold())];
+            if let Some(description) = &t.description {
+                lines.push(Line::styled(format!("  {}", description), Style::default().fg(Color::Gray)));
+            }
+            ListItem::new(lines)
+        })
+        .collect();
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::NONE).title("Choose a template".bold()))
+        .style(Style::default().fg(Color::White))
+        .highlight_style(Style::default().bg(Color::LightBlue).add_modifier(Modifier::BOLD))
+        .highlight_symbol("> ");
+    f.render_stateful_widget(list, inner_layout[0], state);
+
+    if let Some(template) = state.selected().and_then(|i| templates.get(i)) {
+        let details = vec![
+            Line::from(template.name.as_str().bold()),
+            Line::from(template.description.clone().unwrap_or_default()),
+            Line::from(""),
+            Line::styled(format!("from {}", template.file.display()), Style::default().fg(Color::Gray)),
+        ];
+        f.render_widget(
+            Paragraph::new(details).wrap(Wrap { trim: false })
+                .block(Block::default().borders(Borders::ALL).border_type(BorderType::Rounded)),
+            inner_layout[1],
+        );
+    }
+
+    f.render_widget(
+        Paragraph::new("↑↓: choose a template, ENT: start").block(Block::default().borders(Borders::NONE)),
+        main_layout[2].inner(&Margin { horizontal: 1, vertical: 0 }),
+    );
+}
+
+fn ui(f: &mut Frame, app: &mut App) {
+    let main_layout = main_layout(f);
     match app.window {
         WindowType::App => {
             let inner_layout = Layout::default()
@@ -524,15 +544,15 @@ fn ui(f: &mut Frame, app: &mut App) {
 
             let data = app.data_cloned().expect("No data available to display");
             match data {
-                LeafNodeType::Option { name: _name, options } => {
+                LeafNodeType::Option { name: _name, options, .. } => {
                     // Iterate through all elements in the `items`
                     let items: Vec<ListItem> =
-                        options.items
+                        options.items()
                             .iter()
                             .map(|i| {
                                 let mut style = Style::default();
                                 let mut content = String::from("[ ] ");
-                                if i.1 == true {
+                                if i.1 {
                                     content = String::from("[x] ");
                                     style = Style::default().fg(Color::LightYellow);
                                 }
@@ -552,12 +572,12 @@ fn ui(f: &mut Frame, app: &mut App) {
                         .highlight_style(Style::default().bg(Color::LightBlue).add_modifier(Modifier::BOLD))
                         .highlight_symbol("> ");
 
-                    let mut state = options.state.clone();
+                    let mut state = ListState::default().with_selected(options.cursor());
                     // We can now render the item list
                     f.render_stateful_widget(items, inner_layout[0], &mut state);
                 }
 
-                LeafNodeType::TextInput { name: _name, input } => {
+                LeafNodeType::TextInput { name: _name, input, .. } => {
                     match &app.input_mode {
                         InputMode::Normal => {}
                         // Hide the cursor. `Frame` does this by default, so we don't need to do anything here
@@ -582,11 +602,16 @@ fn ui(f: &mut Frame, app: &mut App) {
 
             let mut formatted_preview_tree = String::new();
             let _ = app.preview_tree.write_formatted(&mut formatted_preview_tree);
-            let vec_of_preview_tree: Vec<Line> = formatted_preview_tree.lines().into_iter().enumerate().map(|(i,l)| {
-                if i == app.vertical_index {
-                    Line::from(l.bg(Color::LightMagenta))
+            let vec_of_preview_tree: Vec<Line> = formatted_preview_tree.lines().enumerate().map(|(i,l)| {
+                let line = match app.preview_statuses.get(i) {
+                    Some(Status::Exists) => l.fg(Color::DarkGray),
+                    Some(Status::Conflict) => l.fg(Color::LightRed),
+                    _ => l.into(),
+                };
+                if app.preview_sources.get(i) == Some(&app.node_id) {
+                    Line::from(line.bg(Color::LightMagenta))
                 } else {
-                    Line::from(l)
+                    Line::from(line)
                 }
             }).collect();
 
@@ -624,7 +649,7 @@ fn ui(f: &mut Frame, app: &mut App) {
                 .split(main_layout[1]);
 
             f.render_widget(
-                Paragraph::new("  q, ESC: quit application \n  s: save and quit \n  ENT: Select Item in a list or enter text editing mode \n  i: Enter text editing mode \n  ←: Previous item \n  →: next item \n  ↑↓: go through a list")
+                Paragraph::new("  q, ESC: quit application \n  s: save and quit \n  ENT: Select Item in a list (toggle it in a multi-select list) or enter text editing mode \n  i: Enter text editing mode \n  ←: Previous item \n  →: next item \n  ↑↓: go through a list")
                     .block(Block::default().borders(Borders::NONE).title("Normal Mode (v):".bold())),
                 inner_layout[0],
             );
@@ -636,7 +661,10 @@ fn ui(f: &mut Frame, app: &mut App) {
             );
 
             f.render_widget(
-                Paragraph::new("Note: Under Windows using \"~\" instead of \"C:\\Users\\username\" does currently not work".italic())
+                Paragraph::new("Note: \"~\", \"~user\", $VAR and relative paths in the location are resolved before building. \
+                                In the preview grey folders are already on disk, red ones already exist and belong to this project, \
+                                see --on-conflict".italic())
+                    .wrap(Wrap { trim: true })
                     .block(Block::default().borders(Borders::NONE)),
                 inner_layout[2],
             );
@@ -644,4 +672,44 @@ fn ui(f: &mut Frame, app: &mut App) {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::Scaffolder;
+    use super::*;
+
+    #[test]
+    fn test_pick_from_no_templates() {
+        // fails before the terminal is touched
+        assert!(matches!(pick_template(&[]), Err(Error::Usage(_))));
+    }
 
+    #[test]
+    fn test_preview_statuses() {
+        let location = std::env::temp_dir().join("scaffolder_preview_statuses");
+        let _ = fs::remove_dir_all(&location);
+        fs::create_dir_all(location.join("Beat/Tune")).unwrap();
+        let template = "\
+project:
+  default_location: /tmp
+  children:
+    - ProjectType:
+        options: [Song, Beat]
+        children:
+          - ProjectName:
+              options: \"\"
+              children: [Stems]
+";
+        let tree = Scaffolder::from_yaml_str(template, "t.yaml").unwrap()
+            .location(location.to_string_lossy())
+            .select("ProjectType", "Beat")
+            .answer("ProjectName", "Tune")
+            .questions()
+            .unwrap();
+        let mut app = App::new(tree).unwrap();
+        app.update_preview_tree();
+        // the option folder is shared with other projects, only the named one is in the way
+        assert_eq!(app.preview_statuses, vec![Status::New, Status::Exists, Status::Conflict, Status::New]);
+        fs::remove_dir_all(location).unwrap();
+    }
+}
@@ -0,0 +1,11 @@
+//! Scaffolds project folders from a yaml template.
+//!
+//! The terminal ui lives behind the default `tui` feature, everything needed to
+//! read templates and build folders from code is available without it, see [`Scaffolder`].
+pub mod backend;
+pub mod error;
+pub mod front_end;
+mod scaffolder;
+
+pub use error::{Error, Result};
+pub use scaffolder::Scaffolder;
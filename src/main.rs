@@ -1,27 +1,184 @@
-use std;
-use std::io;
-use cli_clipboard;
-pub mod backend;
-pub mod front_end;
-
-fn main() ->  Result<(), io::Error>{
-    let tree = backend::yaml::parse_yaml("./template.yaml");
-    let ui_res = front_end::ui::init_ui(tree);
-
-    match ui_res {
-        Ok(tree_opt) => {
-            if let Some(tree) = tree_opt {
-                //cpy path to clipboard
-                let project_path = tree.root().expect("Error, tree has no root").data();
-                cli_clipboard::set_contents(project_path.to_owned()).unwrap();
-
-                //build folder structure
-                backend::folder::build_folder_structure(tree);
+use slab_tree::Tree;
+use scaffolder::backend;
+use scaffolder::backend::answers::Answers;
+use scaffolder::backend::folder::ConflictPolicy;
+use scaffolder::backend::tree::nodes::LeafNodeType;
+use scaffolder::front_end;
+use scaffolder::backend::yaml::{TemplateEntry, TemplateSearch};
+use scaffolder::front_end::cli::{Args, Command, Record};
+use scaffolder::{Error, Result};
+
+fn main() {
+    if let Err(e) = run() {
+        e.print();
+        std::process::exit(e.exit_code());
+    }
+}
+
+fn run() -> Result<()> {
+    let args = front_end::cli::parse_args()?;
+    if args.help {
+        println!("{}", front_end::cli::USAGE);
+        return Ok(());
+    }
+
+    let templates = match &args.template {
+        Some(path) => backend::yaml::list_templates(path)?,
+        None => backend::yaml::discover_templates(&TemplateSearch::from_env())?,
+    };
+    if args.command == Command::Templates {
+        print_templates(&templates);
+        return Ok(());
+    }
+    let templates = backend::yaml::visible_templates(&templates);
+    let template = choose_template(&args, &templates)?;
+    for broken in templates.iter().filter(|t| t.problem.is_some() && **t != template) {
+        eprintln!("Warning: skipped {}: {}", broken.file.display(), broken.problem.as_deref().unwrap_or_default());
+    }
+    if args.command == Command::Show {
+        print!("{}", backend::yaml::merged_yaml(&template)?);
+        return Ok(());
+    }
+
+    let (mut tree, settings) = backend::yaml::load_template_entry(&template)?;
+    if let Some(path) = &args.answers {
+        backend::answers::apply_answers(&mut tree, &backend::answers::read_answers(path)?)?;
+    }
+    //--set answers on their own so a multi-select given there replaces the one from the file
+    let mut answers = Answers::default();
+    for (name, value) in &args.set {
+        answers.push(name, value);
+    }
+    backend::answers::apply_answers(&mut tree, &answers)?;
+    if let Some(location) = &args.location {
+        override_location(&mut tree, location);
+    }
+
+    let tree = if args.is_non_interactive() {
+        tree
+    } else {
+        front_end::ui::init_ui(tree)?
+    };
+    let mut plan = backend::tree::resolve::resolve_tree(&tree)?;
+
+    //resolve ~, env vars and relative paths in the location
+    let location = backend::folder::resolve_root(&mut plan)?;
+
+    //--record saves the answers in the project so the run can be replayed with --answers
+    if args.record == Some(Record::Project) {
+        let answers = backend::answers::format_answers(&backend::answers::collect_answers(&tree))?;
+        backend::folder::add_project_file(&mut plan, backend::answers::RECORD_FILE_NAME, answers);
+    }
+
+    //the hooks of the template run in the project folder once it is built
+    backend::folder::add_hooks(&mut plan, &settings.hooks);
+
+    //check the plan against what is already on disk
+    let policy = match args.on_conflict.or(settings.on_conflict) {
+        Some(policy) => policy,
+        None if args.is_non_interactive() => ConflictPolicy::Abort,
+        None => ConflictPolicy::Prompt,
+    };
+    let conflicts = backend::folder::find_conflicts(&plan);
+    let policy = match policy {
+        // a dry run shows the plan as it is, the conflicts are only decided when building
+        ConflictPolicy::Abort | ConflictPolicy::Prompt if args.dry_run => None,
+        ConflictPolicy::Prompt if !conflicts.is_empty() && !args.is_non_interactive() => {
+            match front_end::cli::ask_conflict_policy(&conflicts)? {
+                ConflictPolicy::Abort => return Err(Error::Cancelled),
+                policy => Some(policy),
             }
-            Ok(())
         }
-        Err(e) => {
-            return Err(e); }
+        policy => Some(policy),
+    };
+    if let Some(policy) = policy {
+        backend::folder::resolve_conflicts(&mut plan, policy)?;
     }
+
+    //the dry run shows the same steps the build carries out
+    let steps = backend::folder::build_plan(&plan)?;
+    if args.dry_run {
+        if args.json {
+            let json = steps.to_json().map_err(|e| Error::Validation(format!("could not write the plan as json: {}", e)))?;
+            println!("{}", json);
+        } else {
+            print!("{}", steps);
+        }
+        return Ok(());
+    }
+
+    //build folder structure
+    backend::folder::execute_plan(&steps)?;
+
+    //--record=FILE saves the answers outside of the project
+    if let Some(Record::File(record_path)) = &args.record {
+        backend::answers::write_answers(record_path, &backend::answers::collect_answers(&tree))?;
+    }
+
+    //cpy path to clipboard
+    if !args.no_clipboard {
+        cli_clipboard::set_contents(location.to_string_lossy().to_string())
+            .map_err(|e| Error::Clipboard(e.to_string()))?;
+    }
+    Ok(())
 }
 
+/// The template named with --template-name, the only one there is, or the one picked in the ui.
+///
+/// Broken templates are only chosen by name or when there are no others, loading them then
+/// shows what is wrong.
+fn choose_template(args: &Args, templates: &[TemplateEntry]) -> Result<TemplateEntry> {
+    let names = || templates.iter().map(|t| t.name.as_str()).collect::<Vec<_>>().join(", ");
+    let location = match &args.template {
+        Some(path) => format!("'{}'", path.display()),
+        None => String::from("the template locations"),
+    };
+    if let Some(name) = &args.template_name {
+        return backend::yaml::find_template(templates, name).cloned().ok_or_else(|| {
+            Error::Usage(format!("there is no template named '{}' in {}, choose one of {}", name, location, names()))
+        });
+    }
+    let usable = backend::yaml::usable_templates(templates);
+    let templates = if usable.is_empty() { templates } else { &usable };
+    match templates {
+        [] => Err(Error::Usage(format!("no templates found in {}", location))),
+        [template] => Ok(template.clone()),
+        _ if args.is_non_interactive() || args.command == Command::Show => Err(Error::Usage(format!(
+            "there are several templates in {}, choose one of {} with --template-name", location, names()
+        ))),
+        _ => front_end::ui::pick_template(templates),
+    }
+}
+
+/// Lists the templates with the files they come from, a template hidden by an earlier one is marked.
+fn print_templates(templates: &[TemplateEntry]) {
+    if templates.is_empty() {
+        eprintln!("No templates found, see --help for where they are searched");
+        return;
+    }
+    let name_width = templates.iter().map(|t| t.name.len()).max().unwrap_or(0);
+    let description_width = templates.iter().map(|t| t.description.as_deref().unwrap_or("").len()).max().unwrap_or(0);
+    for (i, template) in templates.iter().enumerate() {
+        let hidden = templates[..i].iter().any(|t| t.name == template.name);
+        let note = match &template.problem {
+            Some(problem) => format!(" (broken: {})", problem),
+            None if hidden => String::from(" (hidden by the one listed earlier)"),
+            None => String::new(),
+        };
+        println!(
+            "{:name_width$}  {:description_width$}  {}{}",
+            template.name,
+            template.description.as_deref().unwrap_or(""),
+            template.file.display(),
+            note,
+        );
+    }
+}
+
+fn override_location(tree: &mut Tree<LeafNodeType>, location: &str) {
+    if let Some(mut root) = tree.root_mut() {
+        if let LeafNodeType::TextInput { name: _name, input, .. } = root.data() {
+            *input = location.to_string();
+        }
+    }
+}
@@ -0,0 +1,252 @@
+use std::path::{Path, PathBuf};
+use slab_tree::Tree;
+use crate::backend;
+use crate::backend::answers::{Answers, Expect};
+use crate::backend::folder::{ConflictPolicy, Plan};
+use crate::backend::tree::nodes::{BuildNode, CloneTree, LeafNodeType};
+use crate::backend::yaml::Settings;
+use crate::error::{Error, Result};
+
+/// Builds a project from a template without the terminal ui.
+///
+/// ```no_run
+/// use scaffolder::Scaffolder;
+///
+/// let project_path = Scaffolder::from_template("template.yaml")?
+///     .answer("ProjectName", "My Song")
+///     .select("ProjectType", "Song")
+///     .build()?;
+/// # Ok::<(), scaffolder::Error>(())
+/// ```
+pub struct Scaffolder {
+    tree: Tree<LeafNodeType>,
+    settings: Settings,
+    answers: Vec<Answer>,
+    on_conflict: Option<ConflictPolicy>,
+}
+
+enum Answer {
+    Location(String),
+    Single { key: String, value: String, expect: Expect },
+    All(Answers),
+}
+
+impl Scaffolder {
+    /// Reads and validates the template at `path`.
+    pub fn from_template(path: impl AsRef<Path>) -> Result<Scaffolder> {
+        let (tree, settings) = backend::yaml::load_yaml(path.as_ref())?;
+        Ok(Scaffolder::from_tree(tree).with_settings(settings))
+    }
+
+    /// Reads the template called `name` from a file or a directory with several templates.
+    pub fn from_named_template(path: impl AsRef<Path>, name: &str) -> Result<Scaffolder> {
+        let templates = backend::yaml::list_templates(path.as_ref())?;
+        let template = backend::yaml::find_template(&templates, name)
+            .ok_or_else(|| Error::Usage(format!("there is no template named '{}' in '{}'", name, path.as_ref().display())))?;
+        let (tree, settings) = backend::yaml::load_template_entry(template)?;
+        Ok(Scaffolder::from_tree(tree).with_settings(settings))
+    }
+
+    /// Parses a template from a string, `file` is only used in error messages.
+    pub fn from_yaml_str(yaml_str: &str, file: &str) -> Result<Scaffolder> {
+        let (tree, settings) = backend::yaml::load_yaml_str(yaml_str, file)?;
+        Ok(Scaffolder::from_tree(tree).with_settings(settings))
+    }
+
+    pub fn from_tree(tree: Tree<LeafNodeType>) -> Scaffolder {
+        Scaffolder { tree, settings: Settings::default(), answers: Vec::new(), on_conflict: None }
+    }
+
+    fn with_settings(mut self, settings: Settings) -> Scaffolder {
+        self.settings = settings;
+        self
+    }
+
+    /// What to do when the project already exists, overrides `on_conflict` of the template.
+    ///
+    /// Without either the build stops, there is no one to ask for `prompt` either.
+    pub fn on_conflict(mut self, policy: ConflictPolicy) -> Scaffolder {
+        self.on_conflict = Some(policy);
+        self
+    }
+
+    /// Overrides the `default_location` of the template.
+    pub fn location(mut self, location: impl Into<String>) -> Scaffolder {
+        self.answers.push(Answer::Location(location.into()));
+        self
+    }
+
+    /// Answers every text input called `name`, `name` can also be a node path.
+    pub fn answer(mut self, name: impl Into<String>, value: impl Into<String>) -> Scaffolder {
+        self.answers.push(Answer::Single { key: name.into(), value: value.into(), expect: Expect::Text });
+        self
+    }
+
+    /// Selects `item` in every option called `name`, `name` can also be a node path.
+    ///
+    /// A multi-select option keeps the items selected before checked.
+    pub fn select(mut self, name: impl Into<String>, item: impl Into<String>) -> Scaffolder {
+        self.answers.push(Answer::Single { key: name.into(), value: item.into(), expect: Expect::Select });
+        self
+    }
+
+    /// Applies answers read from a file or collected elsewhere.
+    pub fn answers(mut self, answers: Answers) -> Scaffolder {
+        self.answers.push(Answer::All(answers));
+        self
+    }
+
+    /// The question tree with all answers given so far filled in.
+    pub fn questions(&self) -> Result<Tree<LeafNodeType>> {
+        let mut tree = self.tree.clone();
+        for answer in &self.answers {
+            match answer {
+                Answer::Location(location) => {
+                    backend::answers::apply_answer(&mut tree, "Location", location, Expect::Text)?;
+                }
+                Answer::Single { key, value, expect } => {
+                    backend::answers::apply_answer(&mut tree, key, value, *expect)?;
+                }
+                Answer::All(answers) => backend::answers::apply_answers(&mut tree, answers)?,
+            }
+        }
+        Ok(tree)
+    }
+
+    /// The folders and files that would be created, with the location resolved to an absolute path.
+    ///
+    /// Folders that already exist are renamed or left out the way the conflict policy builds them,
+    /// with `abort` what is in the way is returned as [`Error::Conflict`] like [`Scaffolder::build`] does.
+    pub fn plan(&self) -> Result<Tree<BuildNode>> {
+        let mut plan = backend::tree::resolve::resolve_tree(&self.questions()?)?;
+        backend::folder::resolve_root(&mut plan)?;
+        backend::folder::add_hooks(&mut plan, &self.settings.hooks);
+        backend::folder::resolve_conflicts(&mut plan, self.conflict_policy())?;
+        Ok(plan)
+    }
+
+    /// The steps [`Scaffolder::build`] carries out.
+    pub fn build_plan(&self) -> Result<Plan> {
+        backend::folder::build_plan(&self.plan()?)
+    }
+
+    /// Creates the folders and files and returns the path of the project root.
+    pub fn build(&self) -> Result<PathBuf> {
+        let plan = self.build_plan()?;
+        backend::folder::execute_plan(&plan)?;
+        Ok(plan.root)
+    }
+
+    fn conflict_policy(&self) -> ConflictPolicy {
+        match self.on_conflict.or(self.settings.on_conflict) {
+            Some(ConflictPolicy::Prompt) | None => ConflictPolicy::Abort,
+            Some(policy) => policy,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use super::*;
+
+    fn working() -> Scaffolder {
+        Scaffolder::from_template("test/working.yaml").unwrap()
+            .location("/tmp/scaffolder-api")
+            .select("ProjectType", "Beat")
+            .answer("ProjectName", "Tune")
+            .select("Project", "Ableton")
+    }
+
+    #[test]
+    fn test_plan() {
+        let err = working().plan().unwrap_err();
+        assert_eq!(err.to_string(), "Missing answers for ProjectType/ProjectName/Output");
+
+        let plan = working().select("Output", "Fl Studio").plan().unwrap();
+        let names: Vec<&str> = plan.root().unwrap().traverse_pre_order().map(|n| n.data().name.as_str()).collect();
+        assert_eq!(names, vec!["/tmp/scaffolder-api", "Beat", "Tune", "Ableton", "Fl Studio", "Master", "Stems"]);
+    }
+
+    #[test]
+    fn test_named_templates() {
+        let plan = Scaffolder::from_named_template("test/catalog", "podcast").unwrap().answer("Episode", "Pilot").plan().unwrap();
+        let names: Vec<String> = plan.root().unwrap().traverse_pre_order().map(|n| n.data().name.clone()).collect();
+        assert_eq!(names, vec!["/tmp/scaffolder-podcast", "Pilot", "Recordings", "Master", "Stems"]);
+
+        assert!(Scaffolder::from_named_template("test/catalog", "opera").is_err());
+        let err = Scaffolder::from_template("test/catalog/music.yaml").err().unwrap();
+        assert!(err.to_string().contains("several templates"), "{}", err);
+    }
+
+    #[test]
+    fn test_invalid_answers() {
+        assert!(working().select("ProjectType", "Opera").plan().is_err());
+        assert!(working().answer("ProjectType", "Song").plan().is_err());
+        assert!(working().answer("Nope", "x").plan().is_err());
+    }
+
+    #[test]
+    fn test_build() {
+        let location = std::env::temp_dir().join("scaffolder_api_build");
+        let _ = fs::remove_dir_all(&location);
+        let root = working()
+            .select("Output", "Ableton")
+            .location(location.to_string_lossy())
+            .build()
+            .unwrap();
+        assert!(root.join("Beat/Tune/Ableton/Master").is_dir());
+        fs::remove_dir_all(location).unwrap();
+    }
+
+    #[test]
+    fn test_build_plan() {
+        let location = std::env::temp_dir().join("scaffolder_api_build_plan");
+        let _ = fs::remove_dir_all(&location);
+        let plan = working().select("Output", "Fl Studio").location(location.to_string_lossy()).build_plan().unwrap();
+        assert_eq!(plan.root, location);
+        let paths: Vec<&Path> = plan.steps.iter().map(|s| s.operation.path()).collect();
+        assert_eq!(paths, vec![
+            location.as_path(),
+            location.join("Beat").as_path(),
+            location.join("Beat/Tune").as_path(),
+            location.join("Beat/Tune/Ableton").as_path(),
+            location.join("Beat/Tune/Fl Studio").as_path(),
+            location.join("Beat/Tune/Fl Studio/Master").as_path(),
+            location.join("Beat/Tune/Fl Studio/Stems").as_path(),
+        ]);
+        assert!(!location.exists(), "planning does not touch the disk");
+    }
+
+    #[test]
+    fn test_build_conflicts() {
+        let location = std::env::temp_dir().join("scaffolder_api_conflicts");
+        let _ = fs::remove_dir_all(&location);
+        let working = || working().select("Output", "Ableton").location(location.to_string_lossy());
+        working().build().unwrap();
+
+        let err = working().build().unwrap_err();
+        assert!(matches!(&err, Error::Conflict(paths) if paths == &vec![location.join("Beat/Tune")]), "{}", err);
+        // planning stops on the same conflicts
+        assert!(matches!(working().plan(), Err(Error::Conflict(_))));
+        assert!(matches!(working().build_plan(), Err(Error::Conflict(_))));
+        working().on_conflict(ConflictPolicy::Suffix).build().unwrap();
+        assert!(location.join("Beat/Tune_2/Ableton/Master").is_dir());
+        working().on_conflict(ConflictPolicy::Merge).build().unwrap();
+        assert!(!location.join("Beat/Tune_3").exists());
+
+        // the policy of the template is used unless one is given
+        let template = "\
+project:
+  default_location: /tmp
+  on_conflict: suffix
+  children:
+    - ProjectName:
+        options: \"\"
+";
+        let scaffolder = Scaffolder::from_yaml_str(template, "t.yaml").unwrap().location(location.to_string_lossy()).answer("ProjectName", "Beat");
+        let plan = scaffolder.plan().unwrap();
+        assert_eq!(plan.root().unwrap().first_child().unwrap().data().name, "Beat_2");
+        fs::remove_dir_all(location).unwrap();
+    }
+}